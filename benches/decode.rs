@@ -0,0 +1,136 @@
+use std::hint::black_box;
+use std::io::Cursor;
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use crowbar::fixture;
+use crowbar::odol::read_compressed_array;
+use crowbar::{parse_header, read_lod_at, read_odol_from_reader, ReadOptions};
+
+const NUM_POINTS: usize = 20000;
+/// Points per side of the grids of the benchmark model.
+const GRID_SIZE: usize = 100;
+
+/// Point data similar to what models store, a wavy grid.
+fn points() -> Vec<u8> {
+    let mut data: Vec<u8> = Vec::with_capacity(NUM_POINTS * 12);
+    for i in 0..NUM_POINTS {
+        let x = (i % 100) as f32 * 0.1;
+        let z = (i / 100) as f32 * 0.1;
+        data.write_f32::<LittleEndian>(x).unwrap();
+        data.write_f32::<LittleEndian>((x * z).sin()).unwrap();
+        data.write_f32::<LittleEndian>(z).unwrap();
+    }
+    data
+}
+
+/// A LOD of the same wavy grid as `points`, made of quads with UVs and split into two
+/// sections and selections.
+fn grid_lod(resolution: f32, size: usize, textured: bool) -> fixture::Lod {
+    let mut lod = fixture::Lod::new(resolution);
+    for i in 0..size * size {
+        let x = (i % size) as f32 * 0.1;
+        let z = (i / size) as f32 * 0.1;
+        lod.points.push((x, (x * z).sin(), z));
+    }
+    for row in 0..size - 1 {
+        for column in 0..size - 1 {
+            let i = (row * size + column) as u32;
+            lod.faces.push(vec![i, i + 1, i + size as u32 + 1, i + size as u32]);
+        }
+    }
+
+    let half = lod.faces.len() / 2;
+    let num_faces = lod.faces.len();
+    if textured {
+        lod.textures = vec!["a\\data\\ground_co.paa".to_string(), "a\\data\\wall_co.paa".to_string()];
+        lod.materials = vec!["a\\data\\ground.rvmat".to_string()];
+        lod.sections.push(fixture::Section { faces: 0..half, texture: 0, material: 0 });
+        lod.sections.push(fixture::Section { faces: half..num_faces, texture: 1, material: -1 });
+
+        let uvs = (0..size * size).map(|i| ((i % size) as f32 / size as f32, (i / size) as f32 / size as f32)).collect();
+        lod.uv_sets.push(uvs);
+    } else {
+        lod.sections.push(fixture::Section { faces: 0..num_faces, texture: 0, material: -1 });
+    }
+
+    lod.selections.push(fixture::Selection {
+        name: "near".to_string(),
+        sections: vec![0],
+        ..Default::default()
+    });
+    lod.selections.push(fixture::Selection {
+        name: "edge".to_string(),
+        points: (0..size as u32).collect(),
+        weights: (0..size).map(|i| (i * 255 / size) as u8).collect(),
+        ..Default::default()
+    });
+    lod
+}
+
+/// A model of medium size with visual, geometry and memory LODs, compressed like binarize
+/// output.
+fn model() -> Vec<u8> {
+    let mut model = fixture::Model::new(vec![
+        grid_lod(1.0, GRID_SIZE, true),
+        grid_lod(2.0, GRID_SIZE / 2, true),
+        grid_lod(1e13, GRID_SIZE / 4, false),
+        grid_lod(1e15, 2, false),
+    ]);
+    model.compress = Some(|data| minilzo::compress(data).unwrap());
+    model.to_bytes()
+}
+
+fn bench_compressed_array(c: &mut Criterion) {
+    let data = points();
+
+    let mut uncompressed = vec![0];
+    uncompressed.extend_from_slice(&data);
+
+    let mut compressed = vec![2];
+    compressed.extend_from_slice(&minilzo::compress(&data).unwrap());
+
+    let mut group = c.benchmark_group("read_compressed_array");
+    group.bench_function("uncompressed", |b| b.iter(|| {
+        read_compressed_array(&mut Cursor::new(black_box(&uncompressed)), data.len()).unwrap()
+    }));
+    group.bench_function("compressed", |b| b.iter(|| {
+        read_compressed_array(&mut Cursor::new(black_box(&compressed)), data.len()).unwrap()
+    }));
+    group.finish();
+}
+
+/// Benchmarks reading a model as a whole and each of its LODs on its own, so regressions can
+/// be traced to the kind of LOD that got slower.
+fn bench_model(c: &mut Criterion, name: &str, data: &[u8]) {
+    let options = ReadOptions::default();
+
+    let mut group = c.benchmark_group(name);
+    group.bench_function("read_odol", |b| b.iter(|| {
+        read_odol_from_reader(&mut Cursor::new(black_box(data)), &options).unwrap()
+    }));
+
+    let header = parse_header(&mut Cursor::new(data)).expect("Failed to parse header");
+    for (i, resolution) in header.resolutions.iter().enumerate() {
+        group.bench_function(format!("read_lod {} {}", i, resolution.lod_type()), |b| b.iter(|| {
+            read_lod_at(&mut Cursor::new(black_box(data)), &header, i, &options).unwrap()
+        }));
+    }
+    group.finish();
+}
+
+fn bench_generated_model(c: &mut Criterion) {
+    bench_model(c, "generated", &model());
+}
+
+/// Real models have more varied data, which can be benchmarked in addition.
+fn bench_real_model(c: &mut Criterion) {
+    if let Some(path) = std::env::var_os("CROWBAR_BENCH_MODEL") {
+        let data = std::fs::read(path).expect("Failed to read CROWBAR_BENCH_MODEL");
+        bench_model(c, "CROWBAR_BENCH_MODEL", &data);
+    }
+}
+
+criterion_group!(benches, bench_compressed_array, bench_generated_model, bench_real_model);
+criterion_main!(benches);
@@ -0,0 +1,12 @@
+#![no_main]
+
+use std::io::Cursor;
+
+use libfuzzer_sys::fuzz_target;
+
+use crowbar::{read_odol_from_reader, ReadOptions};
+
+// Any input must produce either a model or an error, never a panic or an abort.
+fuzz_target!(|data: &[u8]| {
+    let _ = read_odol_from_reader(&mut Cursor::new(data), &ReadOptions::default());
+});
@@ -0,0 +1,182 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use armake2::p3d::LOD;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+
+use crate::lod::LodType;
+use crate::odol::{LodInfo, ParsedModel};
+
+/// Differences between the LODs with the same resolution in two models. The counts are those of
+/// the first and the second model.
+#[derive(Debug, Default)]
+pub struct LodComparison {
+    pub resolution: f32,
+    pub num_points: (usize, usize),
+    pub num_faces: (usize, usize),
+    pub added_selections: Vec<String>,
+    pub removed_selections: Vec<String>,
+    pub added_materials: Vec<String>,
+    pub removed_materials: Vec<String>,
+}
+
+impl LodComparison {
+    fn new(a: (&LOD, &LodInfo), b: (&LOD, &LodInfo)) -> LodComparison {
+        let selections = |info: &LodInfo| -> BTreeSet<String> {
+            info.selections.iter().map(|s| s.name.clone()).collect()
+        };
+        let materials = |lod: &LOD| -> BTreeSet<String> {
+            lod.faces.iter().filter(|f| !f.material.is_empty()).map(|f| f.material.clone()).collect()
+        };
+
+        let (selections_a, selections_b) = (selections(a.1), selections(b.1));
+        let (materials_a, materials_b) = (materials(a.0), materials(b.0));
+
+        LodComparison {
+            resolution: a.0.resolution,
+            num_points: (a.0.points.len(), b.0.points.len()),
+            num_faces: (a.0.faces.len(), b.0.faces.len()),
+            added_selections: selections_b.difference(&selections_a).cloned().collect(),
+            removed_selections: selections_a.difference(&selections_b).cloned().collect(),
+            added_materials: materials_b.difference(&materials_a).cloned().collect(),
+            removed_materials: materials_a.difference(&materials_b).cloned().collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.num_points.0 == self.num_points.1
+            && self.num_faces.0 == self.num_faces.1
+            && self.added_selections.is_empty()
+            && self.removed_selections.is_empty()
+            && self.added_materials.is_empty()
+            && self.removed_materials.is_empty()
+    }
+}
+
+/// Structural differences between two models, for checking that two builds of a model only
+/// differ where expected. LODs are matched by their resolution, in order.
+#[derive(Debug, Default)]
+pub struct Comparison {
+    pub version: (u32, u32),
+    pub num_lods: (usize, usize),
+    /// Resolutions of the LODs only in the second model.
+    pub added_lods: Vec<f32>,
+    /// Resolutions of the LODs only in the first model.
+    pub removed_lods: Vec<f32>,
+    /// The LODs in both models that differ.
+    pub lods: Vec<LodComparison>,
+}
+
+impl Comparison {
+    pub fn new(a: &ParsedModel, b: &ParsedModel) -> Comparison {
+        let mut comparison = Comparison {
+            version: (a.header.version, b.header.version),
+            num_lods: (a.mlod.lods.len(), b.mlod.lods.len()),
+            ..Default::default()
+        };
+
+        let mut matched = vec![false; b.mlod.lods.len()];
+        for (lod_a, info_a) in a.mlod.lods.iter().zip(a.lods.iter()) {
+            let index = b.mlod.lods.iter().enumerate()
+                .position(|(i, lod_b)| !matched[i] && lod_b.resolution == lod_a.resolution);
+            let index = match index {
+                Some(index) => index,
+                None => {
+                    comparison.removed_lods.push(lod_a.resolution);
+                    continue;
+                }
+            };
+            matched[index] = true;
+
+            let lod = LodComparison::new((lod_a, info_a), (&b.mlod.lods[index], &b.lods[index]));
+            if !lod.is_empty() {
+                comparison.lods.push(lod);
+            }
+        }
+
+        comparison.added_lods = b.mlod.lods.iter().zip(matched)
+            .filter(|(_, matched)| !matched)
+            .map(|(lod, _)| lod.resolution)
+            .collect();
+
+        comparison
+    }
+
+    /// Whether the models have no differences that are compared.
+    pub fn is_empty(&self) -> bool {
+        self.version.0 == self.version.1
+            && self.num_lods.0 == self.num_lods.1
+            && self.added_lods.is_empty()
+            && self.removed_lods.is_empty()
+            && self.lods.is_empty()
+    }
+}
+
+fn write_names(f: &mut fmt::Formatter, prefix: &str, kind: &str, names: &[String]) -> fmt::Result {
+    for name in names {
+        writeln!(f, "  {} {} \"{}\"", prefix, kind, name)?;
+    }
+    Ok(())
+}
+
+/// Only lists what differs, nothing at all for equal models.
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.version.0 != self.version.1 {
+            writeln!(f, "version: {} -> {}", self.version.0, self.version.1)?;
+        }
+        if self.num_lods.0 != self.num_lods.1 {
+            writeln!(f, "lods: {} -> {}", self.num_lods.0, self.num_lods.1)?;
+        }
+        for resolution in self.removed_lods.iter() {
+            writeln!(f, "- LOD {}", LodType::from_resolution(*resolution))?;
+        }
+        for resolution in self.added_lods.iter() {
+            writeln!(f, "+ LOD {}", LodType::from_resolution(*resolution))?;
+        }
+
+        for lod in self.lods.iter() {
+            writeln!(f, "{}:", LodType::from_resolution(lod.resolution))?;
+            if lod.num_points.0 != lod.num_points.1 {
+                writeln!(f, "  points: {} -> {}", lod.num_points.0, lod.num_points.1)?;
+            }
+            if lod.num_faces.0 != lod.num_faces.1 {
+                writeln!(f, "  faces: {} -> {}", lod.num_faces.0, lod.num_faces.1)?;
+            }
+            write_names(f, "-", "selection", &lod.removed_selections)?;
+            write_names(f, "+", "selection", &lod.added_selections)?;
+            write_names(f, "-", "material", &lod.removed_materials)?;
+            write_names(f, "+", "material", &lod.added_materials)?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for LodComparison {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut json = serializer.serialize_struct("LodComparison", 8)?;
+        json.serialize_field("resolution", &self.resolution)?;
+        json.serialize_field("type", &LodType::from_resolution(self.resolution).to_string())?;
+        json.serialize_field("num_points", &self.num_points)?;
+        json.serialize_field("num_faces", &self.num_faces)?;
+        json.serialize_field("added_selections", &self.added_selections)?;
+        json.serialize_field("removed_selections", &self.removed_selections)?;
+        json.serialize_field("added_materials", &self.added_materials)?;
+        json.serialize_field("removed_materials", &self.removed_materials)?;
+        json.end()
+    }
+}
+
+impl Serialize for Comparison {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut json = serializer.serialize_struct("Comparison", 6)?;
+        json.serialize_field("equal", &self.is_empty())?;
+        json.serialize_field("version", &self.version)?;
+        json.serialize_field("num_lods", &self.num_lods)?;
+        json.serialize_field("added_lods", &self.added_lods)?;
+        json.serialize_field("removed_lods", &self.removed_lods)?;
+        json.serialize_field("lods", &self.lods)?;
+        json.end()
+    }
+}
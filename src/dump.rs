@@ -0,0 +1,33 @@
+use std::fmt;
+
+use armake2::p3d::LOD;
+
+use crate::lod::LodType;
+
+/// Prints the points and faces of a LOD in MLOD coordinates and with MLOD indices, the way
+/// Object Builder lists them, so the two can be compared side by side.
+pub struct GeometryDump<'a>(pub &'a LOD);
+
+impl<'a> fmt::Display for GeometryDump<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let lod = self.0;
+        writeln!(f, "{} ({} points, {} faces)", LodType::from_resolution(lod.resolution), lod.points.len(), lod.faces.len())?;
+
+        writeln!(f, "points:")?;
+        for (i, point) in lod.points.iter().enumerate() {
+            writeln!(f, "  {:>6}  {:>12.6} {:>12.6} {:>12.6}  flags 0x{:08x}",
+                i, point.coords.0, point.coords.1, point.coords.2, point.flags)?;
+        }
+
+        writeln!(f, "faces:")?;
+        for (i, face) in lod.faces.iter().enumerate() {
+            write!(f, "  {:>6} ", i)?;
+            for vertex in face.vertices.iter() {
+                write!(f, " {}/{}/({:.6}, {:.6})", vertex.point_index, vertex.normal_index, vertex.uv.0, vertex.uv.1)?;
+            }
+            writeln!(f, "  flags 0x{:08x}  \"{}\"  \"{}\"", face.flags, face.texture, face.material)?;
+        }
+
+        Ok(())
+    }
+}
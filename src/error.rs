@@ -0,0 +1,42 @@
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum CrowbarError {
+    Io(io::Error),
+    /// The file doesn't follow the format, e.g. it is corrupt or a parse went out of sync.
+    Malformed(String),
+    /// The file is valid, but uses a feature or version crowbar can't handle yet.
+    Unsupported(String),
+    /// Reading took longer than `ReadOptions::timeout`.
+    Timeout(String),
+}
+
+impl fmt::Display for CrowbarError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CrowbarError::Io(e) => write!(f, "{}", e),
+            CrowbarError::Malformed(msg) => write!(f, "Malformed ODOL: {}", msg),
+            CrowbarError::Unsupported(msg) => write!(f, "Unsupported ODOL: {}", msg),
+            CrowbarError::Timeout(msg) => write!(f, "Timed out: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for CrowbarError {}
+
+impl From<io::Error> for CrowbarError {
+    fn from(e: io::Error) -> CrowbarError {
+        CrowbarError::Io(e)
+    }
+}
+
+impl From<CrowbarError> for io::Error {
+    fn from(e: CrowbarError) -> io::Error {
+        match e {
+            CrowbarError::Io(e) => e,
+            CrowbarError::Timeout(msg) => io::Error::new(io::ErrorKind::TimedOut, msg),
+            e => io::Error::new(io::ErrorKind::InvalidData, e.to_string()),
+        }
+    }
+}
@@ -0,0 +1,256 @@
+#[cfg(not(feature = "wasm"))]
+use std::fs::File;
+#[cfg(not(feature = "wasm"))]
+use std::io::BufWriter;
+use std::io::{Error, Write};
+#[cfg(not(feature = "wasm"))]
+use std::path::Path;
+
+use armake2::p3d::LOD;
+use linked_hash_map::LinkedHashMap;
+
+use crate::export::{export_uv, face_normal, lod_name, material_name, triangles, vertex_normals, ExportOptions, Normals};
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn join<T: ToString>(values: &[T]) -> String {
+    values.iter().map(|v| v.to_string()).collect::<Vec<String>>().join(" ")
+}
+
+fn write_source<O: Write>(output: &mut O, id: &str, values: &[f32], params: &[&str]) -> Result<(), Error> {
+    writeln!(output, "        <source id=\"{}\">", id)?;
+    writeln!(output, "          <float_array id=\"{}-array\" count=\"{}\">{}</float_array>", id, values.len(), join(values))?;
+    writeln!(output, "          <technique_common>")?;
+    writeln!(output, "            <accessor source=\"#{}-array\" count=\"{}\" stride=\"{}\">", id, values.len() / params.len(), params.len())?;
+    for param in params {
+        writeln!(output, "              <param name=\"{}\" type=\"float\"/>", param)?;
+    }
+    writeln!(output, "            </accessor>")?;
+    writeln!(output, "          </technique_common>")?;
+    writeln!(output, "        </source>")?;
+    Ok(())
+}
+
+// procedural textures like #(argb,8,8,3)color(1,1,1,1) have no file to reference
+fn is_file(texture: &str) -> bool {
+    !texture.is_empty() && !texture.starts_with('#')
+}
+
+fn write_materials<O: Write>(output: &mut O, materials: &LinkedHashMap<&str, &str>) -> Result<(), Error> {
+    // COLLADA libraries can't be empty
+    if materials.is_empty() {
+        return Ok(());
+    }
+
+    if materials.values().any(|t| is_file(t)) {
+        writeln!(output, "  <library_images>")?;
+        for (i, texture) in materials.values().enumerate() {
+            if is_file(texture) {
+                writeln!(output, "    <image id=\"material-{}-image\">", i)?;
+                writeln!(output, "      <init_from>{}</init_from>", escape(texture))?;
+                writeln!(output, "    </image>")?;
+            }
+        }
+        writeln!(output, "  </library_images>")?;
+    }
+
+    writeln!(output, "  <library_effects>")?;
+    for (i, texture) in materials.values().enumerate() {
+        writeln!(output, "    <effect id=\"material-{}-effect\">", i)?;
+        writeln!(output, "      <profile_COMMON>")?;
+        let textured = is_file(texture);
+        if textured {
+            writeln!(output, "        <newparam sid=\"material-{}-surface\">", i)?;
+            writeln!(output, "          <surface type=\"2D\"><init_from>material-{}-image</init_from></surface>", i)?;
+            writeln!(output, "        </newparam>")?;
+            writeln!(output, "        <newparam sid=\"material-{}-sampler\">", i)?;
+            writeln!(output, "          <sampler2D><source>material-{}-surface</source></sampler2D>", i)?;
+            writeln!(output, "        </newparam>")?;
+        }
+        writeln!(output, "        <technique sid=\"common\">")?;
+        writeln!(output, "          <lambert>")?;
+        if textured {
+            writeln!(output, "            <diffuse><texture texture=\"material-{}-sampler\" texcoord=\"UVMap\"/></diffuse>", i)?;
+        } else {
+            writeln!(output, "            <diffuse><color>0.8 0.8 0.8 1</color></diffuse>")?;
+        }
+        writeln!(output, "          </lambert>")?;
+        writeln!(output, "        </technique>")?;
+        writeln!(output, "      </profile_COMMON>")?;
+        writeln!(output, "    </effect>")?;
+    }
+    writeln!(output, "  </library_effects>")?;
+
+    writeln!(output, "  <library_materials>")?;
+    for (i, name) in materials.keys().enumerate() {
+        writeln!(output, "    <material id=\"material-{}\" name=\"{}\">", i, escape(name))?;
+        writeln!(output, "      <instance_effect url=\"#material-{}-effect\"/>", i)?;
+        writeln!(output, "    </material>")?;
+    }
+    writeln!(output, "  </library_materials>")?;
+
+    Ok(())
+}
+
+fn write_geometry<O: Write>(output: &mut O, lod: &LOD, index: usize, materials: &LinkedHashMap<&str, &str>, options: &ExportOptions) -> Result<(), Error> {
+    let id = format!("lod-{}-mesh", index);
+
+    let positions: Vec<f32> = lod.points.iter()
+        .flat_map(|p| vec![p.coords.0, p.coords.1, p.coords.2])
+        .collect();
+
+    // Normals are per triangle, or per point when smoothing, and UVs per face vertex, so both
+    // are indexed separately from the positions.
+    let smooth = options.normals == Some(Normals::Smooth);
+    let mut normals: Vec<f32> = if smooth {
+        vertex_normals(lod, options).iter().flat_map(|n| vec![n.0, n.1, n.2]).collect()
+    } else {
+        Vec::new()
+    };
+    let mut uvs: Vec<f32> = Vec::new();
+    let mut groups: LinkedHashMap<&str, Vec<usize>> = LinkedHashMap::new();
+    for face in lod.faces.iter() {
+        let uv_offset = uvs.len() / 2;
+        for vertex in face.vertices.iter() {
+            let uv = export_uv(vertex.uv, options);
+            uvs.extend_from_slice(&[uv.0, uv.1]);
+        }
+
+        let indices = groups.entry(material_name(face)).or_insert_with(Vec::new);
+        for triangle in triangles(face, options) {
+            if smooth {
+                for i in triangle.iter() {
+                    let point_index = face.vertices[*i].point_index as usize;
+                    indices.extend_from_slice(&[point_index, point_index, uv_offset + *i]);
+                }
+                continue;
+            }
+
+            let corners: Vec<(f32, f32, f32)> = triangle.iter()
+                .map(|i| lod.points[face.vertices[*i].point_index as usize].coords)
+                .collect();
+            let normal = face_normal(corners[0], corners[1], corners[2]);
+            let normal_index = normals.len() / 3;
+            normals.extend_from_slice(&[normal.0, normal.1, normal.2]);
+
+            for i in triangle.iter() {
+                indices.extend_from_slice(&[face.vertices[*i].point_index as usize, normal_index, uv_offset + *i]);
+            }
+        }
+    }
+
+    writeln!(output, "    <geometry id=\"{}\" name=\"{}\">", id, escape(&lod_name(lod)))?;
+    writeln!(output, "      <mesh>")?;
+    write_source(output, &format!("{}-positions", id), &positions, &["X", "Y", "Z"])?;
+    write_source(output, &format!("{}-normals", id), &normals, &["X", "Y", "Z"])?;
+    write_source(output, &format!("{}-uvs", id), &uvs, &["S", "T"])?;
+    writeln!(output, "        <vertices id=\"{}-vertices\">", id)?;
+    writeln!(output, "          <input semantic=\"POSITION\" source=\"#{}-positions\"/>", id)?;
+    writeln!(output, "        </vertices>")?;
+
+    for (material, indices) in groups.iter() {
+        let material_index = materials.keys().position(|m| m == material).unwrap();
+        writeln!(output, "        <triangles material=\"material-{}\" count=\"{}\">", material_index, indices.len() / 9)?;
+        writeln!(output, "          <input semantic=\"VERTEX\" source=\"#{}-vertices\" offset=\"0\"/>", id)?;
+        writeln!(output, "          <input semantic=\"NORMAL\" source=\"#{}-normals\" offset=\"1\"/>", id)?;
+        writeln!(output, "          <input semantic=\"TEXCOORD\" source=\"#{}-uvs\" offset=\"2\" set=\"0\"/>", id)?;
+        writeln!(output, "          <p>{}</p>", join(indices))?;
+        writeln!(output, "        </triangles>")?;
+    }
+
+    writeln!(output, "      </mesh>")?;
+    writeln!(output, "    </geometry>")?;
+
+    Ok(())
+}
+
+fn write_node<O: Write>(output: &mut O, lod: &LOD, index: usize, materials: &LinkedHashMap<&str, &str>) -> Result<(), Error> {
+    writeln!(output, "      <node id=\"lod-{}\" name=\"{}\" type=\"NODE\">", index, escape(&lod_name(lod)))?;
+    writeln!(output, "        <instance_geometry url=\"#lod-{}-mesh\">", index)?;
+
+    let mut bound: Vec<&str> = Vec::new();
+    for face in lod.faces.iter() {
+        if !bound.contains(&material_name(face)) {
+            bound.push(material_name(face));
+        }
+    }
+
+    if !bound.is_empty() {
+        writeln!(output, "          <bind_material>")?;
+        writeln!(output, "            <technique_common>")?;
+    }
+    for material in bound.iter() {
+        let material_index = materials.keys().position(|m| m == material).unwrap();
+        writeln!(output, "              <instance_material symbol=\"material-{0}\" target=\"#material-{0}\">", material_index)?;
+        writeln!(output, "                <bind_vertex_input semantic=\"UVMap\" input_semantic=\"TEXCOORD\" input_set=\"0\"/>")?;
+        writeln!(output, "              </instance_material>")?;
+    }
+
+    if !bound.is_empty() {
+        writeln!(output, "            </technique_common>")?;
+        writeln!(output, "          </bind_material>")?;
+    }
+    writeln!(output, "        </instance_geometry>")?;
+
+    // Selections have no COLLADA equivalent without a skin, so they are only kept as empty
+    // child nodes to preserve their names.
+    for (i, name) in lod.taggs.keys().enumerate() {
+        writeln!(output, "        <node id=\"lod-{}-selection-{}\" name=\"{}\" type=\"NODE\"/>", index, i, escape(name))?;
+    }
+
+    writeln!(output, "      </node>")?;
+    Ok(())
+}
+
+/// Writes a COLLADA 1.4.1 document with a geometry and a node per LOD. Quads are triangulated
+/// and normals are computed per triangle.
+pub fn write<O: Write>(lods: &[&LOD], output: &mut O, options: &ExportOptions) -> Result<(), Error> {
+    let mut materials: LinkedHashMap<&str, &str> = LinkedHashMap::new();
+    for lod in lods {
+        for face in lod.faces.iter() {
+            materials.entry(material_name(face)).or_insert(&face.texture);
+        }
+    }
+
+    writeln!(output, "<?xml version=\"1.0\" encoding=\"utf-8\"?>")?;
+    writeln!(output, "<COLLADA xmlns=\"http://www.collada.org/2005/11/COLLADASchema\" version=\"1.4.1\">")?;
+    writeln!(output, "  <asset>")?;
+    writeln!(output, "    <contributor><authoring_tool>crowbar</authoring_tool></contributor>")?;
+    writeln!(output, "    <unit name=\"meter\" meter=\"1\"/>")?;
+    writeln!(output, "    <up_axis>Y_UP</up_axis>")?;
+    writeln!(output, "  </asset>")?;
+
+    write_materials(output, &materials)?;
+
+    writeln!(output, "  <library_geometries>")?;
+    for (i, lod) in lods.iter().enumerate() {
+        write_geometry(output, lod, i, &materials, options)?;
+    }
+    writeln!(output, "  </library_geometries>")?;
+
+    writeln!(output, "  <library_visual_scenes>")?;
+    writeln!(output, "    <visual_scene id=\"scene\">")?;
+    for (i, lod) in lods.iter().enumerate() {
+        write_node(output, lod, i, &materials)?;
+    }
+    writeln!(output, "    </visual_scene>")?;
+    writeln!(output, "  </library_visual_scenes>")?;
+
+    writeln!(output, "  <scene>")?;
+    writeln!(output, "    <instance_visual_scene url=\"#scene\"/>")?;
+    writeln!(output, "  </scene>")?;
+    writeln!(output, "</COLLADA>")?;
+
+    Ok(())
+}
+
+#[cfg(not(feature = "wasm"))]
+pub fn export(lods: &[&LOD], path: &Path, options: &ExportOptions) -> Result<(), Error> {
+    let mut output = BufWriter::new(File::create(path)?);
+    write(lods, &mut output, options)
+}
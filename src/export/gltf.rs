@@ -0,0 +1,320 @@
+#[cfg(not(feature = "wasm"))]
+use std::fs::File;
+#[cfg(not(feature = "wasm"))]
+use std::io::BufWriter;
+use std::io::{Error, Write};
+#[cfg(not(feature = "wasm"))]
+use std::path::Path;
+
+use armake2::p3d::LOD;
+use byteorder::{LittleEndian, WriteBytesExt};
+use linked_hash_map::LinkedHashMap;
+use serde::Serialize;
+
+use crate::export::{export_uv, face_normal, lod_name, material_name, triangles, vertex_normals, ExportOptions, Normals};
+
+const FLOAT: u32 = 5126;
+const ARRAY_BUFFER: u32 = 34962;
+
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const GLB_VERSION: u32 = 2;
+const CHUNK_JSON: u32 = 0x4e4f534a;
+const CHUNK_BIN: u32 = 0x004e4942;
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = ((b[0] as u32) << 16) | ((b[1] as u32) << 8) | (b[2] as u32);
+
+        encoded.push(ALPHABET[(n >> 18) as usize & 0x3f] as char);
+        encoded.push(ALPHABET[(n >> 12) as usize & 0x3f] as char);
+        encoded.push(if chunk.len() > 1 { ALPHABET[(n >> 6) as usize & 0x3f] as char } else { '=' });
+        encoded.push(if chunk.len() > 2 { ALPHABET[n as usize & 0x3f] as char } else { '=' });
+    }
+    encoded
+}
+
+// The parts of the glTF document that are written, named as in the specification.
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Gltf {
+    asset: Asset,
+    scene: u32,
+    scenes: Vec<GltfScene>,
+    nodes: Vec<Node>,
+    meshes: Vec<Mesh>,
+    materials: Vec<Material>,
+    accessors: Vec<Accessor>,
+    buffer_views: Vec<BufferView>,
+    buffers: Vec<Buffer>,
+}
+
+#[derive(Serialize)]
+struct Asset {
+    version: &'static str,
+    generator: &'static str,
+}
+
+#[derive(Serialize)]
+struct GltfScene {
+    nodes: Vec<usize>,
+}
+
+#[derive(Serialize)]
+struct Node {
+    name: String,
+    mesh: usize,
+}
+
+#[derive(Serialize)]
+struct Mesh {
+    name: String,
+    primitives: Vec<MeshPrimitive>,
+}
+
+#[derive(Serialize)]
+struct MeshPrimitive {
+    attributes: Attributes,
+    material: usize,
+}
+
+/// Indices of the accessors of the vertex attributes.
+#[derive(Serialize)]
+struct Attributes {
+    #[serde(rename = "POSITION")]
+    position: usize,
+    #[serde(rename = "TEXCOORD_0")]
+    texcoord: usize,
+    #[serde(rename = "NORMAL", skip_serializing_if = "Option::is_none")]
+    normal: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct Material {
+    name: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Accessor {
+    buffer_view: usize,
+    component_type: u32,
+    count: usize,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min: Option<Vec<f32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max: Option<Vec<f32>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BufferView {
+    buffer: u32,
+    byte_offset: usize,
+    byte_length: usize,
+    target: u32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Buffer {
+    byte_length: usize,
+    /// The geometry as a data URI, left out in .glb files where it follows the JSON.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uri: Option<String>,
+}
+
+/// Scene under construction, geometry is appended to a single buffer.
+struct Scene {
+    buffer: Vec<u8>,
+    buffer_views: Vec<BufferView>,
+    accessors: Vec<Accessor>,
+    materials: LinkedHashMap<String, usize>,
+}
+
+impl Scene {
+    /// Adds a float accessor over the given values and returns its index.
+    fn add_accessor(&mut self, values: &[f32], components: usize, with_bounds: bool) -> Result<usize, Error> {
+        let offset = self.buffer.len();
+        for value in values {
+            self.buffer.write_f32::<LittleEndian>(*value)?;
+        }
+
+        self.buffer_views.push(BufferView {
+            buffer: 0,
+            byte_offset: offset,
+            byte_length: values.len() * 4,
+            target: ARRAY_BUFFER,
+        });
+
+        let mut accessor = Accessor {
+            buffer_view: self.buffer_views.len() - 1,
+            component_type: FLOAT,
+            count: values.len() / components,
+            kind: if components == 3 { "VEC3" } else { "VEC2" },
+            min: None,
+            max: None,
+        };
+
+        if with_bounds {
+            let mut min = vec![f32::MAX; components];
+            let mut max = vec![f32::MIN; components];
+            for element in values.chunks(components) {
+                for (i, value) in element.iter().enumerate() {
+                    min[i] = min[i].min(*value);
+                    max[i] = max[i].max(*value);
+                }
+            }
+            accessor.min = Some(min);
+            accessor.max = Some(max);
+        }
+
+        self.accessors.push(accessor);
+        Ok(self.accessors.len() - 1)
+    }
+
+    fn material_index(&mut self, name: &str) -> usize {
+        let next = self.materials.len();
+        *self.materials.entry(name.to_string()).or_insert(next)
+    }
+}
+
+/// Vertex attributes of the faces of one material, unindexed.
+#[derive(Default)]
+struct Primitive {
+    positions: Vec<f32>,
+    uvs: Vec<f32>,
+    normals: Vec<f32>,
+}
+
+/// Builds the glTF document and the binary buffer it references. Every LOD becomes a node with
+/// one mesh, with one non-indexed triangle primitive per material.
+/// The buffers are left for the caller, which decides where the geometry goes.
+fn build(lods: &[&LOD], options: &ExportOptions) -> Result<(Gltf, Vec<u8>), Error> {
+    let mut scene = Scene {
+        buffer: Vec::new(),
+        buffer_views: Vec::new(),
+        accessors: Vec::new(),
+        materials: LinkedHashMap::new(),
+    };
+
+    let mut nodes: Vec<Node> = Vec::new();
+    let mut meshes: Vec<Mesh> = Vec::new();
+
+    for lod in lods {
+        // glTF meshes need at least one primitive
+        if lod.faces.is_empty() {
+            continue;
+        }
+
+        let smooth_normals = if options.normals == Some(Normals::Smooth) { vertex_normals(lod, options) } else { Vec::new() };
+
+        let mut groups: LinkedHashMap<&str, Primitive> = LinkedHashMap::new();
+        for face in lod.faces.iter() {
+            let Primitive { positions, uvs, normals } = groups.entry(material_name(face)).or_insert_with(Primitive::default);
+            for triangle in triangles(face, options) {
+                let corners: Vec<(f32, f32, f32)> = triangle.iter()
+                    .map(|i| lod.points[face.vertices[*i].point_index as usize].coords)
+                    .collect();
+                let facet_normal = face_normal(corners[0], corners[1], corners[2]);
+
+                for i in triangle.iter() {
+                    let vertex = &face.vertices[*i];
+                    let point = &lod.points[vertex.point_index as usize];
+                    positions.extend_from_slice(&[point.coords.0, point.coords.1, point.coords.2]);
+                    let uv = export_uv(vertex.uv, options);
+                    uvs.extend_from_slice(&[uv.0, uv.1]);
+
+                    let normal = match options.normals {
+                        Some(Normals::Facet) => facet_normal,
+                        Some(Normals::Smooth) => smooth_normals[vertex.point_index as usize],
+                        None => continue,
+                    };
+                    normals.extend_from_slice(&[normal.0, normal.1, normal.2]);
+                }
+            }
+        }
+
+        let mut primitives: Vec<MeshPrimitive> = Vec::new();
+        for (material, Primitive { positions, uvs, normals }) in groups.iter() {
+            let attributes = Attributes {
+                position: scene.add_accessor(positions, 3, true)?,
+                texcoord: scene.add_accessor(uvs, 2, false)?,
+                normal: if options.normals.is_some() { Some(scene.add_accessor(normals, 3, false)?) } else { None },
+            };
+            primitives.push(MeshPrimitive { attributes, material: scene.material_index(material) });
+        }
+
+        meshes.push(Mesh { name: lod_name(lod), primitives });
+        nodes.push(Node { name: lod_name(lod), mesh: meshes.len() - 1 });
+    }
+
+    let gltf = Gltf {
+        asset: Asset { version: "2.0", generator: "crowbar" },
+        scene: 0,
+        scenes: vec![GltfScene { nodes: (0..nodes.len()).collect() }],
+        nodes,
+        meshes,
+        materials: scene.materials.keys().map(|name| Material { name: name.clone() }).collect(),
+        accessors: scene.accessors,
+        buffer_views: scene.buffer_views,
+        buffers: Vec::new(),
+    };
+
+    Ok((gltf, scene.buffer))
+}
+
+/// Writes a .gltf with the geometry embedded as a base64 data URI.
+pub fn write<O: Write>(lods: &[&LOD], output: &mut O, options: &ExportOptions) -> Result<(), Error> {
+    let (mut gltf, buffer) = build(lods, options)?;
+    gltf.buffers.push(Buffer {
+        byte_length: buffer.len(),
+        uri: Some(format!("data:application/octet-stream;base64,{}", base64(&buffer))),
+    });
+
+    serde_json::to_writer(output, &gltf)?;
+    Ok(())
+}
+
+/// Writes a .glb, with the JSON and the geometry in chunks of a single binary file.
+pub fn write_glb<O: Write>(lods: &[&LOD], output: &mut O, options: &ExportOptions) -> Result<(), Error> {
+    let (mut gltf, mut buffer) = build(lods, options)?;
+    gltf.buffers.push(Buffer { byte_length: buffer.len(), uri: None });
+
+    // Chunks have to be 4-byte aligned, JSON is padded with spaces and the buffer with zeros.
+    let mut json = serde_json::to_vec(&gltf)?;
+    json.resize(json.len().div_ceil(4) * 4, b' ');
+    buffer.resize(buffer.len().div_ceil(4) * 4, 0);
+
+    output.write_all(GLB_MAGIC)?;
+    output.write_u32::<LittleEndian>(GLB_VERSION)?;
+    output.write_u32::<LittleEndian>((12 + 8 + json.len() + 8 + buffer.len()) as u32)?;
+
+    output.write_u32::<LittleEndian>(json.len() as u32)?;
+    output.write_u32::<LittleEndian>(CHUNK_JSON)?;
+    output.write_all(&json)?;
+
+    output.write_u32::<LittleEndian>(buffer.len() as u32)?;
+    output.write_u32::<LittleEndian>(CHUNK_BIN)?;
+    output.write_all(&buffer)?;
+
+    Ok(())
+}
+
+#[cfg(not(feature = "wasm"))]
+pub fn export(lods: &[&LOD], path: &Path, options: &ExportOptions) -> Result<(), Error> {
+    let mut output = BufWriter::new(File::create(path)?);
+    write(lods, &mut output, options)
+}
+
+#[cfg(not(feature = "wasm"))]
+pub fn export_glb(lods: &[&LOD], path: &Path, options: &ExportOptions) -> Result<(), Error> {
+    let mut output = BufWriter::new(File::create(path)?);
+    write_glb(lods, &mut output, options)
+}
@@ -0,0 +1,197 @@
+#[cfg(not(feature = "wasm"))]
+use std::io::Error;
+#[cfg(not(feature = "wasm"))]
+use std::path::Path;
+
+use armake2::p3d::{Face, LOD};
+
+use crate::faces::FaceShape;
+use crate::lod::LodType;
+
+pub mod collada;
+pub mod gltf;
+pub mod obj;
+pub mod ply;
+pub mod stl;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Obj,
+    Gltf,
+    Glb,
+    Stl,
+    Ply,
+    Dae,
+}
+
+pub const FORMATS: &[(&str, Format)] = &[
+    ("obj", Format::Obj),
+    ("gltf", Format::Gltf),
+    ("glb", Format::Glb),
+    ("stl", Format::Stl),
+    ("ply", Format::Ply),
+    ("dae", Format::Dae),
+];
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Format> {
+        let name = name.to_lowercase();
+        FORMATS.iter().find(|(n, _)| *n == name).map(|(_, f)| *f)
+    }
+
+    pub fn name(&self) -> &'static str {
+        FORMATS.iter().find(|(_, f)| f == self).map(|(n, _)| *n).unwrap()
+    }
+}
+
+/// How normals are written.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Normals {
+    /// One normal per triangle, so every face looks flat.
+    Facet,
+    /// One normal per point, averaged from the triangles around it.
+    Smooth,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ExportOptions {
+    /// Reverse the vertex order of every face. By default faces keep the order they have in
+    /// MLOD, which faces outwards in right-handed tools like Blender and Maya, since the
+    /// left-handed coordinates are written as they are.
+    pub flip_winding: bool,
+    /// Write V as 1 - V. UVs are always exported in the Arma convention with the origin at
+    /// the top left, which glTF shares. OBJ and Collada importers usually expect the origin at
+    /// the bottom left and show textures upside down without this.
+    pub flip_uv_v: bool,
+    /// Normals to write. By default, Collada and STL get facet normals and the other formats
+    /// none. STL can only store facet normals and PLY only smooth ones, so the other mode is
+    /// ignored for those.
+    pub normals: Option<Normals>,
+}
+
+/// Name used for the object/mesh of a LOD in exported files.
+pub fn lod_name(lod: &LOD) -> String {
+    LodType::from_resolution(lod.resolution).to_string().replace(' ', "_")
+}
+
+/// Name used for the material of a face, the material path if it has one, otherwise the
+/// texture path.
+pub fn material_name(face: &Face) -> &str {
+    if !face.material.is_empty() {
+        &face.material
+    } else if !face.texture.is_empty() {
+        &face.texture
+    } else {
+        "default"
+    }
+}
+
+/// Groups the faces of a LOD by `material_name`, in the order each material first appears.
+/// The sort is stable, so faces with the same material keep their order. Selection taggs are
+/// reordered along with the faces.
+pub fn sort_faces_by_material(lod: &mut LOD) {
+    let mut first_use: Vec<&str> = Vec::new();
+    let mut order: Vec<(usize, usize)> = Vec::with_capacity(lod.faces.len());
+    for (i, face) in lod.faces.iter().enumerate() {
+        let material = material_name(face);
+        let group = first_use.iter().position(|m| *m == material).unwrap_or_else(|| {
+            first_use.push(material);
+            first_use.len() - 1
+        });
+        order.push((group, i));
+    }
+    order.sort_by_key(|(group, _)| *group);
+
+    let num_points = lod.points.len();
+    let num_faces = lod.faces.len();
+    for (_, data) in lod.taggs.iter_mut() {
+        if data.len() == num_points + num_faces {
+            let faces: Vec<u8> = order.iter().map(|(_, i)| data[num_points + i]).collect();
+            data[num_points..].copy_from_slice(&faces);
+        }
+    }
+
+    let mut faces: Vec<Option<Face>> = std::mem::take(&mut lod.faces).into_iter().map(Some).collect();
+    lod.faces = order.iter().filter_map(|(_, i)| faces[*i].take()).collect();
+}
+
+/// Normal of the triangle a-b-c, or zero for degenerate triangles.
+pub fn face_normal(a: (f32, f32, f32), b: (f32, f32, f32), c: (f32, f32, f32)) -> (f32, f32, f32) {
+    let u = (b.0 - a.0, b.1 - a.1, b.2 - a.2);
+    let v = (c.0 - a.0, c.1 - a.1, c.2 - a.2);
+    let n = (u.1 * v.2 - u.2 * v.1, u.2 * v.0 - u.0 * v.2, u.0 * v.1 - u.1 * v.0);
+
+    let length = (n.0 * n.0 + n.1 * n.1 + n.2 * n.2).sqrt();
+    if length > 0.0 {
+        (n.0 / length, n.1 / length, n.2 / length)
+    } else {
+        (0.0, 0.0, 0.0)
+    }
+}
+
+/// UV of a vertex as it should be written.
+pub fn export_uv(uv: (f32, f32), options: &ExportOptions) -> (f32, f32) {
+    if options.flip_uv_v {
+        (uv.0, 1.0 - uv.1)
+    } else {
+        uv
+    }
+}
+
+/// Smooth normal of every point of a LOD, the normalized sum of the normals of all triangles
+/// that use it. Points that aren't part of any triangle get a zero normal.
+pub fn vertex_normals(lod: &LOD, options: &ExportOptions) -> Vec<(f32, f32, f32)> {
+    let mut sums: Vec<(f32, f32, f32)> = vec![(0.0, 0.0, 0.0); lod.points.len()];
+    for face in lod.faces.iter() {
+        for triangle in triangles(face, options) {
+            let indices: Vec<usize> = triangle.iter().map(|i| face.vertices[*i].point_index as usize).collect();
+            let normal = face_normal(lod.points[indices[0]].coords, lod.points[indices[1]].coords, lod.points[indices[2]].coords);
+            for index in indices {
+                let sum = &mut sums[index];
+                *sum = (sum.0 + normal.0, sum.1 + normal.1, sum.2 + normal.2);
+            }
+        }
+    }
+
+    sums.into_iter().map(|n| {
+        let length = (n.0 * n.0 + n.1 * n.1 + n.2 * n.2).sqrt();
+        if length > 0.0 {
+            (n.0 / length, n.1 / length, n.2 / length)
+        } else {
+            (0.0, 0.0, 0.0)
+        }
+    }).collect()
+}
+
+/// Vertex indices of a face in the order they should be written.
+pub fn face_order(face: &Face, options: &ExportOptions) -> Vec<usize> {
+    if options.flip_winding {
+        (0..face.vertices.len()).rev().collect()
+    } else {
+        (0..face.vertices.len()).collect()
+    }
+}
+
+/// Splits a face into triangles of vertex indices. Quads are always split along the 0-2
+/// diagonal, so triangulation is deterministic across exporters.
+pub fn triangles(face: &Face, options: &ExportOptions) -> Vec<[usize; 3]> {
+    let triangles = face.triangle_indices();
+
+    if options.flip_winding {
+        triangles.iter().map(|t| [t[0], t[2], t[1]]).collect()
+    } else {
+        triangles.to_vec()
+    }
+}
+
+#[cfg(not(feature = "wasm"))]
+pub fn export(lods: &[&LOD], format: Format, path: &Path, options: &ExportOptions) -> Result<(), Error> {
+    match format {
+        Format::Obj => obj::export(lods, path, options),
+        Format::Gltf => gltf::export(lods, path, options),
+        Format::Glb => gltf::export_glb(lods, path, options),
+        Format::Stl => stl::export(lods, path, options),
+        Format::Ply => ply::export(lods, path, options),
+        Format::Dae => collada::export(lods, path, options),
+    }
+}
@@ -0,0 +1,124 @@
+#[cfg(not(feature = "wasm"))]
+use std::fs::File;
+#[cfg(not(feature = "wasm"))]
+use std::io::BufWriter;
+use std::io::{Error, Write};
+#[cfg(not(feature = "wasm"))]
+use std::path::Path;
+
+use armake2::p3d::LOD;
+use linked_hash_map::LinkedHashMap;
+
+use crate::export::{export_uv, face_normal, face_order, lod_name, vertex_normals, Normals, material_name, ExportOptions};
+
+pub fn write<O: Write>(lods: &[&LOD], output: &mut O, mtllib: Option<&str>, options: &ExportOptions) -> Result<(), Error> {
+    writeln!(output, "# Exported by crowbar")?;
+    if let Some(mtllib) = mtllib {
+        writeln!(output, "mtllib {}", mtllib)?;
+    }
+
+    // OBJ indices are 1-based and global across all objects in the file
+    let mut point_offset = 1;
+    let mut uv_offset = 1;
+    let mut normal_offset = 1;
+
+    for lod in lods {
+        writeln!(output, "o {}", lod_name(lod))?;
+
+        for point in lod.points.iter() {
+            writeln!(output, "v {} {} {}", point.coords.0, point.coords.1, point.coords.2)?;
+        }
+
+        for face in lod.faces.iter() {
+            for vertex in face.vertices.iter() {
+                let uv = export_uv(vertex.uv, options);
+                writeln!(output, "vt {} {}", uv.0, uv.1)?;
+            }
+        }
+
+        // Facet normals are written per face, from the first triangle of the face, smooth ones
+        // per point.
+        let num_normals = match options.normals {
+            Some(Normals::Facet) => {
+                for face in lod.faces.iter() {
+                    let corners: Vec<(f32, f32, f32)> = face_order(face, options).iter()
+                        .take(3)
+                        .map(|i| lod.points[face.vertices[*i].point_index as usize].coords)
+                        .collect();
+                    let normal = if corners.len() == 3 { face_normal(corners[0], corners[1], corners[2]) } else { (0.0, 0.0, 0.0) };
+                    writeln!(output, "vn {} {} {}", normal.0, normal.1, normal.2)?;
+                }
+                lod.faces.len()
+            },
+            Some(Normals::Smooth) => {
+                for normal in vertex_normals(lod, options) {
+                    writeln!(output, "vn {} {} {}", normal.0, normal.1, normal.2)?;
+                }
+                lod.points.len()
+            },
+            None => 0,
+        };
+
+        let mut current_material: Option<&str> = None;
+        let mut uv_index = uv_offset;
+        for (face_index, face) in lod.faces.iter().enumerate() {
+            let material = material_name(face);
+            if current_material != Some(material) {
+                writeln!(output, "usemtl {}", material)?;
+                current_material = Some(material);
+            }
+
+            write!(output, "f")?;
+            for i in face_order(face, options) {
+                let point_index = face.vertices[i].point_index as usize;
+                write!(output, " {}/{}", point_offset + point_index, uv_index + i)?;
+                match options.normals {
+                    Some(Normals::Facet) => write!(output, "/{}", normal_offset + face_index)?,
+                    Some(Normals::Smooth) => write!(output, "/{}", normal_offset + point_index)?,
+                    None => {},
+                }
+            }
+            writeln!(output)?;
+            uv_index += face.vertices.len();
+        }
+
+        point_offset += lod.points.len();
+        uv_offset = uv_index;
+        normal_offset += num_normals;
+    }
+
+    Ok(())
+}
+
+pub fn write_mtl<O: Write>(lods: &[&LOD], output: &mut O) -> Result<(), Error> {
+    let mut materials: LinkedHashMap<&str, &str> = LinkedHashMap::new();
+    for lod in lods {
+        for face in lod.faces.iter() {
+            materials.entry(material_name(face)).or_insert(&face.texture);
+        }
+    }
+
+    writeln!(output, "# Exported by crowbar")?;
+    for (name, texture) in materials.iter() {
+        writeln!(output, "newmtl {}", name)?;
+        // procedural textures like #(argb,8,8,3)color(1,1,1,1) have no file to reference
+        if !texture.is_empty() && !texture.starts_with('#') {
+            writeln!(output, "map_Kd {}", texture)?;
+        }
+        writeln!(output)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(not(feature = "wasm"))]
+pub fn export(lods: &[&LOD], path: &Path, options: &ExportOptions) -> Result<(), Error> {
+    let mtl_path = path.with_extension("mtl");
+    let mtl_name = mtl_path.file_name().unwrap().to_string_lossy().to_string();
+
+    let mut output = BufWriter::new(File::create(path)?);
+    write(lods, &mut output, Some(&mtl_name), options)?;
+
+    let mut mtl_output = BufWriter::new(File::create(&mtl_path)?);
+    write_mtl(lods, &mut mtl_output)
+}
@@ -0,0 +1,68 @@
+#[cfg(not(feature = "wasm"))]
+use std::fs::File;
+#[cfg(not(feature = "wasm"))]
+use std::io::BufWriter;
+use std::io::{Error, Write};
+#[cfg(not(feature = "wasm"))]
+use std::path::Path;
+
+use armake2::p3d::LOD;
+
+use crate::export::{face_order, vertex_normals, ExportOptions, Normals};
+
+/// Writes an ASCII PLY. Like STL, PLY has a single mesh per file, so all LODs are merged.
+/// Normals belong to the vertices, so only smooth normals can be written.
+pub fn write<O: Write>(lods: &[&LOD], output: &mut O, options: &ExportOptions) -> Result<(), Error> {
+    let smooth = options.normals == Some(Normals::Smooth);
+
+    let num_points: usize = lods.iter().map(|lod| lod.points.len()).sum();
+    let num_faces: usize = lods.iter().map(|lod| lod.faces.len()).sum();
+
+    writeln!(output, "ply")?;
+    writeln!(output, "format ascii 1.0")?;
+    writeln!(output, "comment Exported by crowbar")?;
+    writeln!(output, "element vertex {}", num_points)?;
+    writeln!(output, "property float x")?;
+    writeln!(output, "property float y")?;
+    writeln!(output, "property float z")?;
+    if smooth {
+        writeln!(output, "property float nx")?;
+        writeln!(output, "property float ny")?;
+        writeln!(output, "property float nz")?;
+    }
+    writeln!(output, "element face {}", num_faces)?;
+    writeln!(output, "property list uchar int vertex_indices")?;
+    writeln!(output, "end_header")?;
+
+    for lod in lods {
+        let normals = if smooth { vertex_normals(lod, options) } else { Vec::new() };
+        for (i, point) in lod.points.iter().enumerate() {
+            write!(output, "{} {} {}", point.coords.0, point.coords.1, point.coords.2)?;
+            if let Some(normal) = normals.get(i) {
+                write!(output, " {} {} {}", normal.0, normal.1, normal.2)?;
+            }
+            writeln!(output)?;
+        }
+    }
+
+    let mut point_offset = 0;
+    for lod in lods {
+        for face in lod.faces.iter() {
+            write!(output, "{}", face.vertices.len())?;
+            for i in face_order(face, options) {
+                write!(output, " {}", point_offset + face.vertices[i].point_index as usize)?;
+            }
+            writeln!(output)?;
+        }
+
+        point_offset += lod.points.len();
+    }
+
+    Ok(())
+}
+
+#[cfg(not(feature = "wasm"))]
+pub fn export(lods: &[&LOD], path: &Path, options: &ExportOptions) -> Result<(), Error> {
+    let mut output = BufWriter::new(File::create(path)?);
+    write(lods, &mut output, options)
+}
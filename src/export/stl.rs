@@ -0,0 +1,56 @@
+#[cfg(not(feature = "wasm"))]
+use std::fs::File;
+#[cfg(not(feature = "wasm"))]
+use std::io::BufWriter;
+use std::io::{Error, Write};
+#[cfg(not(feature = "wasm"))]
+use std::path::Path;
+
+use armake2::p3d::LOD;
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use crate::export::{face_normal, triangles, ExportOptions};
+
+fn write_vec3<O: Write>(output: &mut O, v: (f32, f32, f32)) -> Result<(), Error> {
+    output.write_f32::<LittleEndian>(v.0)?;
+    output.write_f32::<LittleEndian>(v.1)?;
+    output.write_f32::<LittleEndian>(v.2)?;
+    Ok(())
+}
+
+/// Writes a binary STL. STL has no notion of objects, so all LODs end up in the same mesh.
+pub fn write<O: Write>(lods: &[&LOD], output: &mut O, options: &ExportOptions) -> Result<(), Error> {
+    let mut header = [0; 80];
+    header[..19].copy_from_slice(b"Exported by crowbar");
+    output.write_all(&header)?;
+
+    let num_triangles: usize = lods.iter()
+        .flat_map(|lod| lod.faces.iter())
+        .map(|face| triangles(face, options).len())
+        .sum();
+    output.write_u32::<LittleEndian>(num_triangles as u32)?;
+
+    for lod in lods {
+        for face in lod.faces.iter() {
+            for triangle in triangles(face, options) {
+                let corners: Vec<(f32, f32, f32)> = triangle.iter()
+                    .map(|i| lod.points[face.vertices[*i].point_index as usize].coords)
+                    .collect();
+
+                write_vec3(output, face_normal(corners[0], corners[1], corners[2]))?;
+                for corner in corners {
+                    write_vec3(output, corner)?;
+                }
+                output.write_u16::<LittleEndian>(0)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(feature = "wasm"))]
+pub fn export(lods: &[&LOD], path: &Path, options: &ExportOptions) -> Result<(), Error> {
+    let mut output = BufWriter::new(File::create(path)?);
+    write(lods, &mut output, options)
+}
@@ -0,0 +1,145 @@
+use armake2::p3d::{Face, LOD};
+
+use crate::export::face_normal;
+
+pub trait FaceShape {
+    /// Whether the face has four vertices. All other faces are treated as triangles.
+    fn is_quad(&self) -> bool;
+
+    /// Splits the face into triangles of vertex indices, along the 0-2 diagonal for quads.
+    fn triangle_indices(&self) -> &'static [[usize; 3]] {
+        if self.is_quad() {
+            &[[0, 1, 2], [0, 2, 3]]
+        } else {
+            &[[0, 1, 2]]
+        }
+    }
+}
+
+impl FaceShape for Face {
+    fn is_quad(&self) -> bool {
+        self.vertices.len() == 4
+    }
+}
+
+/// A face with everything its vertices reference looked up in the LOD.
+#[derive(Debug, Clone)]
+pub struct ResolvedFace<'a> {
+    /// Index of the face in the LOD.
+    pub index: usize,
+    pub positions: Vec<(f32, f32, f32)>,
+    pub uvs: Vec<(f32, f32)>,
+    /// Normal of the plane through the first three vertices. The normals stored in the LOD
+    /// aren't used, since ODOLs don't keep them.
+    pub normal: (f32, f32, f32),
+    pub texture: &'a str,
+    pub material: &'a str,
+}
+
+pub struct ResolvedFaces<'a> {
+    lod: &'a LOD,
+    index: usize,
+}
+
+impl<'a> FaceShape for ResolvedFace<'a> {
+    fn is_quad(&self) -> bool {
+        self.positions.len() == 4
+    }
+}
+
+impl<'a> Iterator for ResolvedFaces<'a> {
+    type Item = ResolvedFace<'a>;
+
+    fn next(&mut self) -> Option<ResolvedFace<'a>> {
+        while let Some(face) = self.lod.faces.get(self.index) {
+            let index = self.index;
+            self.index += 1;
+
+            // Faces referencing points the LOD doesn't have are left out.
+            let positions: Option<Vec<(f32, f32, f32)>> = face.vertices.iter()
+                .map(|v| self.lod.points.get(v.point_index as usize).map(|p| p.coords))
+                .collect();
+            let positions = match positions {
+                Some(positions) => positions,
+                None => continue,
+            };
+
+            let normal = if positions.len() >= 3 {
+                face_normal(positions[0], positions[1], positions[2])
+            } else {
+                (0.0, 0.0, 0.0)
+            };
+
+            return Some(ResolvedFace {
+                index,
+                uvs: face.vertices.iter().map(|v| v.uv).collect(),
+                positions,
+                normal,
+                texture: &face.texture,
+                material: &face.material,
+            });
+        }
+
+        None
+    }
+}
+
+pub trait ResolveFaces {
+    /// Iterates over the faces of the LOD with their vertices resolved, in MLOD winding order.
+    fn resolved_faces(&self) -> ResolvedFaces<'_>;
+}
+
+impl ResolveFaces for LOD {
+    fn resolved_faces(&self) -> ResolvedFaces<'_> {
+        ResolvedFaces { lod: self, index: 0 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::fixture;
+    use crate::odol::{read_odol_from_bytes, ReadOptions};
+
+    fn read_lod(lod: fixture::Lod) -> LOD {
+        let data = fixture::Model::new(vec![lod]).to_bytes();
+        read_odol_from_bytes(data, &ReadOptions::default()).unwrap().mlod.lods.remove(0)
+    }
+
+    #[test]
+    fn triangles_and_quads() {
+        let mut lod = fixture::Lod::new(1.0);
+        lod.points = vec![(0.0, 0.0, 0.0), (1.0, 0.0, 0.0), (1.0, 0.0, 1.0), (0.0, 0.0, 1.0), (2.0, 0.0, 0.0)];
+        lod.faces = vec![vec![0, 1, 2, 3], vec![1, 4, 2], vec![4, 1, 0], vec![0, 4, 2, 3]];
+        lod.sections.push(fixture::Section { faces: 0..4, texture: 0, material: -1 });
+        let lod = read_lod(lod);
+
+        let shapes: Vec<bool> = lod.faces.iter().map(|f| f.is_quad()).collect();
+        assert_eq!(shapes, [true, false, false, true]);
+
+        // The vertices are in MLOD winding order, reversed from the ODOL.
+        let triangles: Vec<Vec<[u32; 3]>> = lod.faces.iter().map(|face| {
+            face.triangle_indices().iter()
+                .map(|t| [face.vertices[t[0]].point_index, face.vertices[t[1]].point_index, face.vertices[t[2]].point_index])
+                .collect()
+        }).collect();
+        assert_eq!(triangles, vec![
+            vec![[3, 2, 1], [3, 1, 0]],
+            vec![[2, 4, 1]],
+            vec![[0, 1, 4]],
+            vec![[3, 2, 4], [3, 4, 0]],
+        ]);
+
+        let resolved: Vec<ResolvedFace> = lod.resolved_faces().collect();
+        assert_eq!(resolved.len(), 4);
+        for (i, face) in resolved.iter().enumerate() {
+            assert_eq!(face.index, i);
+            assert_eq!(face.is_quad(), shapes[i]);
+            assert_eq!(face.triangle_indices(), lod.faces[i].triangle_indices());
+            assert_eq!(face.positions.len(), lod.faces[i].vertices.len());
+        }
+        assert_eq!(resolved[0].positions, [(0.0, 0.0, 1.0), (1.0, 0.0, 1.0), (1.0, 0.0, 0.0), (0.0, 0.0, 0.0)]);
+        assert_eq!(resolved[0].normal, (0.0, 1.0, 0.0));
+    }
+}
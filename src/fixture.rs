@@ -0,0 +1,444 @@
+// Writes synthetic ODOL v73 models, for the unit tests and the benchmarks. Only what the reader
+// looks at is written, everything it skips is filled with zeros. Outside of the unit tests it is
+// only built with the fixture feature.
+#![allow(dead_code)]
+
+use std::ops::Range;
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+/// Compresses an array, like `minilzo::compress`.
+pub type Compress = fn(&[u8]) -> Vec<u8>;
+
+pub struct Section {
+    /// Indices of the faces, which the ODOL stores as byte offsets into the faces.
+    pub faces: Range<usize>,
+    pub texture: u16,
+    /// Index into the materials, or -1 for none.
+    pub material: i32,
+}
+
+#[derive(Default)]
+pub struct Selection {
+    pub name: String,
+    pub faces: Vec<u32>,
+    pub sections: Vec<u32>,
+    pub points: Vec<u32>,
+    /// Weight of every point from 0 to 255, empty if all are full.
+    pub weights: Vec<u8>,
+}
+
+pub struct Lod {
+    pub resolution: f32,
+    pub points: Vec<(f32, f32, f32)>,
+    /// Clip flags of every point. Stored as a single value if they are all the same.
+    pub point_flags: Vec<u32>,
+    pub textures: Vec<String>,
+    /// Paths of the materials, which have a single stage without texture.
+    pub materials: Vec<String>,
+    pub faces: Vec<Vec<u32>>,
+    pub sections: Vec<Section>,
+    pub selections: Vec<Selection>,
+    pub properties: Vec<(String, String)>,
+    /// Number of bones of every keyframe.
+    pub keyframes: Vec<u32>,
+    /// UVs by point, the first set is stored before the others even if there are none.
+    pub uv_sets: Vec<Vec<(f32, f32)>>,
+    /// Number of UV sets to store instead of the length of `uv_sets`.
+    pub num_uv_sets: Option<u32>,
+}
+
+impl Lod {
+    pub fn new(resolution: f32) -> Lod {
+        Lod {
+            resolution,
+            points: Vec::new(),
+            point_flags: Vec::new(),
+            textures: Vec::new(),
+            materials: Vec::new(),
+            faces: Vec::new(),
+            sections: Vec::new(),
+            selections: Vec::new(),
+            properties: Vec::new(),
+            keyframes: Vec::new(),
+            uv_sets: Vec::new(),
+            num_uv_sets: None,
+        }
+    }
+}
+
+pub struct Animation {
+    pub anim_type: u32,
+    pub name: String,
+    pub source: String,
+    pub value: (f32, f32),
+    pub phase: (f32, f32),
+    /// Two values, eight for direct animations: their axis position and direction, angle and
+    /// offset along the axis.
+    pub params: Vec<f32>,
+    /// Index of the bone in the skeleton, in every LOD.
+    pub bone: Option<u32>,
+}
+
+impl Animation {
+    /// An animation of `anim_type` from 0 to 1 of its source, with zero parameters.
+    pub fn new(anim_type: u32, name: &str, source: &str, bone: Option<u32>) -> Animation {
+        Animation {
+            anim_type,
+            name: name.to_string(),
+            source: source.to_string(),
+            value: (0.0, 1.0),
+            phase: (0.0, 1.0),
+            params: vec![0.0; if anim_type == 8 { 8 } else { 2 }],
+            bone,
+        }
+    }
+}
+
+pub struct Model {
+    pub version: u32,
+    pub appid: u32,
+    pub lods: Vec<Lod>,
+    pub bounding_center: (f32, f32, f32),
+    pub can_blend: bool,
+    pub class_type: String,
+    pub skeleton_name: String,
+    pub bones: Vec<(String, String)>,
+    pub animations: Vec<Animation>,
+    /// Compresses arrays of 1024 bytes and more, like binarize does. Arrays are stored raw
+    /// if this is None.
+    pub compress: Option<Compress>,
+}
+
+impl Model {
+    pub fn new(lods: Vec<Lod>) -> Model {
+        Model {
+            version: 73,
+            appid: 107410,
+            lods,
+            bounding_center: (0.0, 0.0, 0.0),
+            can_blend: false,
+            class_type: String::new(),
+            skeleton_name: String::new(),
+            bones: Vec::new(),
+            animations: Vec::new(),
+            compress: None,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out: Vec<u8> = Vec::new();
+        self.write_header(&mut out);
+
+        // The LOD offsets end the header, and are patched once the LODs are written.
+        let offsets_start = out.len();
+        out.resize(offsets_start + 4 * self.lods.len(), 0);
+        for (i, lod) in self.lods.iter().enumerate() {
+            let offset = out.len() as u32;
+            out[offsets_start + 4 * i..offsets_start + 4 * i + 4].copy_from_slice(&offset.to_le_bytes());
+            self.write_lod(&mut out, lod);
+        }
+        out
+    }
+
+    fn write_header(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(b"ODOL");
+        u32(out, self.version);
+        u32(out, self.appid);
+        cstring(out, "");
+        u32(out, self.lods.len() as u32);
+        for lod in self.lods.iter() {
+            f32(out, lod.resolution);
+        }
+
+        // index, LOD spheres, point flags, offset, map colors and view density
+        zeros(out, 4 + 2 * 4 + 3 * 4 + 12 + 2 * 4 + 4);
+        // bounding box, LOD density and draw importance, visual bounding box
+        zeros(out, 2 * 12 + 2 * 4 + 2 * 12);
+        vec3(out, self.bounding_center);
+        // geometry center, cog offset, inverse inertia, autocenter to ai cover
+        zeros(out, 12 + 12 + 3 * 12 + 5);
+        // thermal parameters, force not alpha, sb source, prefer shadow volume, shadow offset
+        zeros(out, 6 * 4 + 1 + 4 + 1 + 4);
+        u8(out, !self.animations.is_empty() as u8);
+
+        cstring(out, &self.skeleton_name);
+        if !self.skeleton_name.is_empty() {
+            u8(out, 0);
+            u32(out, self.bones.len() as u32);
+            for (bone, parent) in self.bones.iter() {
+                cstring(out, bone);
+                cstring(out, parent);
+            }
+            cstring(out, "");
+        }
+
+        // map type, no floats, mass and armor
+        zeros(out, 1 + 4 + 4 * 4);
+        for _i in 0..14 {
+            out.push(0xff);
+        }
+        zeros(out, 4);
+        u32(out, 0);
+        u8(out, self.can_blend as u8);
+        cstring(out, &self.class_type);
+        cstring(out, "");
+        zeros(out, if self.version >= 73 { 1 } else { 0 } + 4);
+        zeros(out, self.lods.len() * 3 * 4);
+
+        self.write_animations(out);
+    }
+
+    fn write_animations(&self, out: &mut Vec<u8>) {
+        u8(out, !self.animations.is_empty() as u8);
+        if self.animations.is_empty() {
+            return;
+        }
+
+        u32(out, self.animations.len() as u32);
+        for animation in self.animations.iter() {
+            u32(out, animation.anim_type);
+            cstring(out, &animation.name);
+            cstring(out, &animation.source);
+            f32(out, animation.value.0);
+            f32(out, animation.value.1);
+            f32(out, animation.phase.0);
+            f32(out, animation.phase.1);
+            zeros(out, 4 + 4);
+            u32(out, 0);
+            for param in animation.params.iter() {
+                f32(out, *param);
+            }
+        }
+
+        // Every LOD lists the animations of every bone, then the bone and axis of every
+        // animation.
+        u32(out, self.lods.len() as u32);
+        for _lod in self.lods.iter() {
+            u32(out, self.bones.len() as u32);
+            for bone in 0..self.bones.len() as u32 {
+                let animations: Vec<u32> = (0..self.animations.len() as u32)
+                    .filter(|i| self.animations[*i as usize].bone == Some(bone))
+                    .collect();
+                u32(out, animations.len() as u32);
+                for i in animations {
+                    u32(out, i);
+                }
+            }
+        }
+        for _lod in self.lods.iter() {
+            for animation in self.animations.iter() {
+                match animation.bone {
+                    Some(bone) => {
+                        u32(out, bone);
+                        // Axis position and direction of rotations and translations.
+                        let axis_floats = if animation.anim_type <= 7 { 6 } else { 0 };
+                        for i in 0..axis_floats {
+                            f32(out, i as f32);
+                        }
+                    },
+                    None => out.write_i32::<LittleEndian>(-1).unwrap(),
+                }
+            }
+        }
+    }
+
+    fn write_lod(&self, out: &mut Vec<u8>, lod: &Lod) {
+        // proxies, bones of the subskeleton and the skeleton
+        zeros(out, 3 * 4);
+        u32(out, lod.points.len() as u32);
+        zeros(out, 3 * 4 + 3 * 12 + 4);
+
+        u32(out, lod.textures.len() as u32);
+        for texture in lod.textures.iter() {
+            cstring(out, texture);
+        }
+
+        u32(out, lod.materials.len() as u32);
+        for material in lod.materials.iter() {
+            cstring(out, material);
+            zeros(out, 4 + 6 * 16 + 5 * 4);
+            cstring(out, "");
+            zeros(out, 2 * 4);
+            u32(out, 1);
+            u32(out, 0);
+            u32(out, 0);
+            cstring(out, "");
+            u32(out, 0);
+            u8(out, 0);
+            // stage of the terrain texture index map
+            u32(out, 0);
+            cstring(out, "");
+            u32(out, 0);
+            u8(out, 0);
+        }
+
+        // edges
+        zeros(out, 2 * 4);
+
+        u32(out, lod.faces.len() as u32);
+        zeros(out, 6);
+        let mut face_offsets: Vec<u32> = Vec::with_capacity(lod.faces.len() + 1);
+        let mut faces_size = 0;
+        for face in lod.faces.iter() {
+            face_offsets.push(faces_size);
+            faces_size += 1 + 4 * face.len() as u32;
+            u8(out, face.len() as u8);
+            for v in face.iter() {
+                u32(out, *v);
+            }
+        }
+        face_offsets.push(faces_size);
+
+        u32(out, lod.sections.len() as u32);
+        for section in lod.sections.iter() {
+            u32(out, face_offsets[section.faces.start]);
+            u32(out, face_offsets[section.faces.end]);
+            zeros(out, 3 * 4);
+            out.write_u16::<LittleEndian>(section.texture).unwrap();
+            zeros(out, 4);
+            out.write_i32::<LittleEndian>(section.material).unwrap();
+            if section.material == -1 {
+                u8(out, 0);
+            }
+            // no stages and no collision info
+            zeros(out, 2 * 4);
+        }
+
+        u32(out, lod.selections.len() as u32);
+        for selection in lod.selections.iter() {
+            cstring(out, &selection.name);
+            self.write_u32_array(out, &selection.faces);
+            zeros(out, 4 + 1);
+            self.write_u32_array(out, &selection.sections);
+            self.write_u32_array(out, &selection.points);
+            u32(out, selection.weights.len() as u32);
+            if !selection.weights.is_empty() {
+                self.write_array(out, &selection.weights);
+            }
+        }
+
+        u32(out, lod.properties.len() as u32);
+        for (name, value) in lod.properties.iter() {
+            cstring(out, name);
+            cstring(out, value);
+        }
+
+        u32(out, lod.keyframes.len() as u32);
+        for (i, num_bones) in lod.keyframes.iter().enumerate() {
+            f32(out, i as f32);
+            u32(out, *num_bones);
+            zeros(out, *num_bones as usize * 12);
+        }
+
+        zeros(out, 3 * 4 + 1 + 4);
+
+        u32(out, lod.points.len() as u32);
+        let fill = lod.point_flags.first().copied().unwrap_or(0);
+        if lod.point_flags.iter().all(|f| *f == fill) {
+            u8(out, 1);
+            u32(out, fill);
+        } else {
+            u8(out, 0);
+            for flags in lod.point_flags.iter() {
+                u32(out, *flags);
+            }
+        }
+
+        let no_uvs = Vec::new();
+        let num_uv_sets = lod.num_uv_sets.unwrap_or(lod.uv_sets.len() as u32);
+        self.write_uvs(out, lod.uv_sets.first().unwrap_or(&no_uvs));
+        u32(out, num_uv_sets);
+        for uvs in lod.uv_sets.iter().skip(1) {
+            self.write_uvs(out, uvs);
+        }
+
+        u32(out, lod.points.len() as u32);
+        if !lod.points.is_empty() {
+            let mut data: Vec<u8> = Vec::with_capacity(lod.points.len() * 12);
+            for point in lod.points.iter() {
+                vec3(&mut data, *point);
+            }
+            self.write_array(out, &data);
+        }
+    }
+
+    /// Writes a UV set with its scale, which is the range of the UVs.
+    fn write_uvs(&self, out: &mut Vec<u8>, uvs: &[(f32, f32)]) {
+        let min = uvs.iter().fold((0.0f32, 0.0f32), |m, uv| (m.0.min(uv.0), m.1.min(uv.1)));
+        let max = uvs.iter().fold((1.0f32, 1.0f32), |m, uv| (m.0.max(uv.0), m.1.max(uv.1)));
+        f32(out, min.0);
+        f32(out, min.1);
+        f32(out, max.0);
+        f32(out, max.1);
+
+        u32(out, uvs.len() as u32);
+        if uvs.is_empty() {
+            return;
+        }
+
+        let quantize = |value: f32, min: f32, max: f32| ((value - min) / (max - min) * (2.0 * 32767.0) - 32767.0).round() as i16;
+        let mut data: Vec<u8> = Vec::with_capacity(uvs.len() * 4);
+        for uv in uvs.iter() {
+            data.write_i16::<LittleEndian>(quantize(uv.0, min.0, max.0)).unwrap();
+            data.write_i16::<LittleEndian>(quantize(uv.1, min.1, max.1)).unwrap();
+        }
+
+        if uvs.iter().all(|uv| uv == &uvs[0]) {
+            u8(out, 1);
+            out.extend_from_slice(&data[..4]);
+        } else {
+            u8(out, 0);
+            self.write_array(out, &data);
+        }
+    }
+
+    /// Writes the count and the values, which are left out if there are none.
+    fn write_u32_array(&self, out: &mut Vec<u8>, values: &[u32]) {
+        u32(out, values.len() as u32);
+        if !values.is_empty() {
+            let data: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes().to_vec()).collect();
+            self.write_array(out, &data);
+        }
+    }
+
+    fn write_array(&self, out: &mut Vec<u8>, data: &[u8]) {
+        match self.compress {
+            Some(compress) if data.len() >= 1024 => {
+                u8(out, 2);
+                out.extend_from_slice(&compress(data));
+            },
+            _ => {
+                u8(out, 0);
+                out.extend_from_slice(data);
+            },
+        }
+    }
+}
+
+fn u8(out: &mut Vec<u8>, value: u8) {
+    out.push(value);
+}
+
+fn u32(out: &mut Vec<u8>, value: u32) {
+    out.write_u32::<LittleEndian>(value).unwrap();
+}
+
+fn f32(out: &mut Vec<u8>, value: f32) {
+    out.write_f32::<LittleEndian>(value).unwrap();
+}
+
+fn vec3(out: &mut Vec<u8>, value: (f32, f32, f32)) {
+    f32(out, value.0);
+    f32(out, value.1);
+    f32(out, value.2);
+}
+
+fn cstring(out: &mut Vec<u8>, s: &str) {
+    out.extend_from_slice(s.as_bytes());
+    out.push(0);
+}
+
+fn zeros(out: &mut Vec<u8>, n: usize) {
+    out.resize(out.len() + n, 0);
+}
@@ -1,8 +1,15 @@
 use std::io;
-use std::io::{Read, Write};
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use sha2::{Digest, Sha256};
 
 pub trait ReadExt: Read {
     fn read_cstring(&mut self) -> io::Result<String>;
+    fn read_vec3(&mut self) -> io::Result<(f32, f32, f32)>;
+    /// Reads a 4x3 transformation matrix, stored as the three rotation/scale columns followed
+    /// by the translation.
+    fn read_matrix4x3(&mut self) -> io::Result<[(f32, f32, f32); 4]>;
 }
 
 impl<T: Read> ReadExt for T {
@@ -18,7 +25,18 @@ impl<T: Read> ReadExt for T {
 
         }
 
-        Ok(String::from_utf8(bytes).unwrap())
+        String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn read_vec3(&mut self) -> io::Result<(f32, f32, f32)> {
+        Ok((
+            self.read_f32::<LittleEndian>()?,
+            self.read_f32::<LittleEndian>()?,
+            self.read_f32::<LittleEndian>()?))
+    }
+
+    fn read_matrix4x3(&mut self) -> io::Result<[(f32, f32, f32); 4]> {
+        Ok([self.read_vec3()?, self.read_vec3()?, self.read_vec3()?, self.read_vec3()?])
     }
 }
 
@@ -33,3 +51,98 @@ impl<T: Write> WriteExt for T {
         Ok(())
     }
 }
+
+/// Anything the parser can read from, so that inputs of different types can be boxed.
+pub trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// Wraps a reader so that the position `start` of the inner reader becomes position 0. Used
+/// for files with a header in front of the model, where all offsets in the model are
+/// relative to its own start.
+pub struct Offset<R> {
+    inner: R,
+    start: u64,
+}
+
+impl<R: Seek> Offset<R> {
+    pub fn new(mut inner: R, start: u64) -> io::Result<Offset<R>> {
+        inner.seek(SeekFrom::Start(start))?;
+        Ok(Offset { inner, start })
+    }
+}
+
+impl<R: Read> Read for Offset<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl<R: Seek> Seek for Offset<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(offset) => SeekFrom::Start(self.start + offset),
+            other => other,
+        };
+
+        let position = self.inner.seek(pos)?;
+        if position < self.start {
+            self.inner.seek(SeekFrom::Start(self.start))?;
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Seek before the start of the model."));
+        }
+        Ok(position - self.start)
+    }
+}
+
+/// Passes everything written on to the inner writer and hashes it along the way, so that the
+/// hash of a file is known once it is written.
+pub struct HashWriter<W> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> HashWriter<W> {
+    pub fn new(inner: W) -> HashWriter<W> {
+        HashWriter { inner, hasher: Sha256::new() }
+    }
+
+    /// SHA-256 of everything written so far.
+    pub fn hash(&self) -> [u8; 32] {
+        self.hasher.clone().finalize().into()
+    }
+}
+
+impl<W: Write> Write for HashWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Formats a hash as lowercase hex, like sha256sum does.
+pub fn to_hex(hash: &[u8]) -> String {
+    hash.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_of_written_pieces() {
+        let mut output = Vec::new();
+        let mut f = HashWriter::new(&mut output);
+        assert_eq!(to_hex(&f.hash()), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+
+        for piece in [&b"abcdbcdecdefdefgefgh"[..], b"fghighijhijkijkljklmklmnlmnomnopnopq"].iter() {
+            f.write_all(piece).unwrap();
+        }
+        assert_eq!(to_hex(&f.hash()), "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1");
+        assert_eq!(output, &b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"[..]);
+    }
+}
@@ -0,0 +1,23 @@
+pub mod compare;
+pub mod dump;
+pub mod error;
+pub mod export;
+pub mod faces;
+#[cfg(any(test, feature = "fixture"))]
+pub mod fixture;
+pub mod io;
+pub mod lod;
+#[macro_use]
+pub mod log;
+pub mod lzo;
+pub mod mlod;
+pub mod modelcfg;
+pub mod odol;
+pub mod stats;
+
+pub use crate::error::CrowbarError;
+pub use crate::faces::{FaceShape, ResolveFaces, ResolvedFace};
+pub use crate::mlod::{convert_bytes, convert_streaming, write_mlod};
+#[cfg(not(feature = "wasm"))]
+pub use crate::odol::{open_input, read_odol};
+pub use crate::odol::{count_faces, lod_byte_ranges, mlod_weight, mlod_weight_byte, parse_header, parse_header_with_options, parse_with, read_lod_at, read_odol_from_bytes, read_odol_from_reader, read_odol_lods, read_odol_streaming, read_paths, skip_compressed_array, ArrayReader, CompressedBlock, CompressionReport, Game, LodInfo, Material, MaterialStage, MLOD_VERSION, MLOD_VERSIONS, OdolAnimation, OdolHeader, OdolVisitor, ParsedModel, ReadOptions, Selection, SelectionReport, SelectionSize, StageTransform};
@@ -0,0 +1,270 @@
+use std::fmt;
+use std::num::ParseFloatError;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LodType {
+    Visual(f32),
+    ViewGunner,
+    ViewPilot,
+    ViewCargo,
+    ShadowVolume(f32),
+    ShadowBuffer(f32),
+    Geometry,
+    GeometryBuoyancy,
+    GeometryPhysX,
+    Memory,
+    LandContact,
+    Roadway,
+    Paths,
+    HitPoints,
+    ViewGeometry,
+    FireGeometry,
+    ViewCargoGeometry,
+    ViewCargoFireGeometry,
+    ViewCommander,
+    ViewCommanderGeometry,
+    ViewCommanderFireGeometry,
+    ViewPilotGeometry,
+    ViewPilotFireGeometry,
+    ViewGunnerGeometry,
+    ViewGunnerFireGeometry,
+    SubParts,
+    ShadowVolumeViewCargo,
+    ShadowVolumeViewPilot,
+    ShadowVolumeViewGunner,
+    Wreck,
+    Unknown(f32),
+}
+
+const SPECIAL_LODS: &[(f32, LodType)] = &[
+    (1.0e3, LodType::ViewGunner),
+    (1.1e3, LodType::ViewPilot),
+    (1.2e3, LodType::ViewCargo),
+    (1.0e13, LodType::Geometry),
+    (2.0e13, LodType::GeometryBuoyancy),
+    (4.0e13, LodType::GeometryPhysX),
+    (1.0e15, LodType::Memory),
+    (2.0e15, LodType::LandContact),
+    (3.0e15, LodType::Roadway),
+    (4.0e15, LodType::Paths),
+    (5.0e15, LodType::HitPoints),
+    (6.0e15, LodType::ViewGeometry),
+    (7.0e15, LodType::FireGeometry),
+    (8.0e15, LodType::ViewCargoGeometry),
+    (9.0e15, LodType::ViewCargoFireGeometry),
+    (1.0e16, LodType::ViewCommander),
+    (1.1e16, LodType::ViewCommanderGeometry),
+    (1.2e16, LodType::ViewCommanderFireGeometry),
+    (1.3e16, LodType::ViewPilotGeometry),
+    (1.4e16, LodType::ViewPilotFireGeometry),
+    (1.5e16, LodType::ViewGunnerGeometry),
+    (1.6e16, LodType::ViewGunnerFireGeometry),
+    (1.7e16, LodType::SubParts),
+    (1.8e16, LodType::ShadowVolumeViewCargo),
+    (1.9e16, LodType::ShadowVolumeViewPilot),
+    (2.0e16, LodType::ShadowVolumeViewGunner),
+    (2.1e16, LodType::Wreck),
+];
+
+/// Groups of LOD types that can be picked by the start of their names, like all view LODs.
+pub const LOD_TYPE_GROUPS: &[&str] = &["view", "shadow"];
+
+impl LodType {
+    /// Names of all LOD types, in the order of their resolutions.
+    pub fn names() -> Vec<&'static str> {
+        let mut names = vec![LodType::Visual(0.0).name(), LodType::ShadowVolume(0.0).name(), LodType::ShadowBuffer(0.0).name()];
+        names.extend(SPECIAL_LODS.iter().map(|(_, lod_type)| lod_type.name()));
+        names
+    }
+
+    /// Whether this has the type `name`, or is in the group `name`.
+    pub fn is_type(&self, name: &str) -> bool {
+        let own = self.name();
+        own == name || (LOD_TYPE_GROUPS.contains(&name) && own.starts_with(name) && own[name.len()..].starts_with('-'))
+    }
+
+    pub fn from_resolution(resolution: f32) -> LodType {
+        if resolution < 1.0e3 {
+            return LodType::Visual(resolution);
+        }
+
+        for (value, lod_type) in SPECIAL_LODS {
+            if (resolution - value).abs() <= value * 1.0e-3 {
+                return *lod_type;
+            }
+        }
+
+        if (1.0e4..1.1e4).contains(&resolution) {
+            LodType::ShadowVolume(resolution - 1.0e4)
+        } else if (1.1e4..1.2e4).contains(&resolution) {
+            LodType::ShadowBuffer(resolution - 1.1e4)
+        } else {
+            LodType::Unknown(resolution)
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LodType::Visual(_) => "visual",
+            LodType::ViewGunner => "view-gunner",
+            LodType::ViewPilot => "view-pilot",
+            LodType::ViewCargo => "view-cargo",
+            LodType::ShadowVolume(_) => "shadow-volume",
+            LodType::ShadowBuffer(_) => "shadow-buffer",
+            LodType::Geometry => "geometry",
+            LodType::GeometryBuoyancy => "geometry-buoyancy",
+            LodType::GeometryPhysX => "geometry-physx",
+            LodType::Memory => "memory",
+            LodType::LandContact => "land-contact",
+            LodType::Roadway => "roadway",
+            LodType::Paths => "paths",
+            LodType::HitPoints => "hitpoints",
+            LodType::ViewGeometry => "view-geometry",
+            LodType::FireGeometry => "fire-geometry",
+            LodType::ViewCargoGeometry => "view-cargo-geometry",
+            LodType::ViewCargoFireGeometry => "view-cargo-fire-geometry",
+            LodType::ViewCommander => "view-commander",
+            LodType::ViewCommanderGeometry => "view-commander-geometry",
+            LodType::ViewCommanderFireGeometry => "view-commander-fire-geometry",
+            LodType::ViewPilotGeometry => "view-pilot-geometry",
+            LodType::ViewPilotFireGeometry => "view-pilot-fire-geometry",
+            LodType::ViewGunnerGeometry => "view-gunner-geometry",
+            LodType::ViewGunnerFireGeometry => "view-gunner-fire-geometry",
+            LodType::SubParts => "sub-parts",
+            LodType::ShadowVolumeViewCargo => "shadow-volume-view-cargo",
+            LodType::ShadowVolumeViewPilot => "shadow-volume-view-pilot",
+            LodType::ShadowVolumeViewGunner => "shadow-volume-view-gunner",
+            LodType::Wreck => "wreck",
+            LodType::Unknown(_) => "unknown",
+        }
+    }
+}
+
+/// Resolution of a LOD as stored in the file. The resolutions of special LODs are large
+/// powers of ten that only match with a tolerance, so resolutions compare equal if they are
+/// of the same type and, for visual and shadow LODs, the same level of detail.
+#[derive(Debug, Clone, Copy)]
+pub struct Resolution(pub f32);
+
+impl Resolution {
+    pub fn lod_type(&self) -> LodType {
+        LodType::from_resolution(self.0)
+    }
+
+    pub fn is_visual(&self) -> bool {
+        matches!(self.lod_type(), LodType::Visual(_))
+    }
+
+    pub fn is_geometry(&self) -> bool {
+        self.lod_type() == LodType::Geometry
+    }
+
+    pub fn is_memory(&self) -> bool {
+        self.lod_type() == LodType::Memory
+    }
+
+    /// Whether this is any kind of shadow LOD, including those of the views.
+    pub fn is_shadow(&self) -> bool {
+        self.lod_type().is_type("shadow")
+    }
+
+    /// Level of detail of visual and shadow LODs, None for all others.
+    pub fn detail_level(&self) -> Option<f32> {
+        match self.lod_type() {
+            LodType::Visual(x) | LodType::ShadowVolume(x) | LodType::ShadowBuffer(x) => Some(x),
+            _ => None,
+        }
+    }
+}
+
+impl From<f32> for Resolution {
+    fn from(resolution: f32) -> Resolution {
+        Resolution(resolution)
+    }
+}
+
+impl PartialEq for Resolution {
+    fn eq(&self, other: &Resolution) -> bool {
+        self.lod_type() == other.lod_type()
+    }
+}
+
+impl FromStr for Resolution {
+    type Err = ParseFloatError;
+
+    fn from_str(s: &str) -> Result<Resolution, ParseFloatError> {
+        s.parse::<f32>().map(Resolution)
+    }
+}
+
+impl fmt::Display for Resolution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl fmt::Display for LodType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LodType::Visual(x) | LodType::ShadowVolume(x) | LodType::ShadowBuffer(x) => write!(f, "{} {:.3}", self.name(), x),
+            LodType::Unknown(x) => write!(f, "{} {:e}", self.name(), x),
+            _ => write!(f, "{}", self.name()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn special_resolutions_match_with_a_tolerance() {
+        assert_eq!(LodType::from_resolution(1.0e13), LodType::Geometry);
+        assert_eq!(LodType::from_resolution(1.0e13 * 1.0009), LodType::Geometry);
+        assert_eq!(LodType::from_resolution(1.0e13 * 0.9991), LodType::Geometry);
+        assert_eq!(LodType::from_resolution(1.0e13 * 1.002), LodType::Unknown(1.0e13 * 1.002));
+        assert_eq!(LodType::from_resolution(1.1e3), LodType::ViewPilot);
+        assert_eq!(LodType::from_resolution(1.1e3 + 2.0), LodType::Unknown(1.1e3 + 2.0));
+        assert_eq!(LodType::from_resolution(2.1e16), LodType::Wreck);
+    }
+
+    #[test]
+    fn visual_and_shadow_ranges() {
+        assert_eq!(LodType::from_resolution(0.0), LodType::Visual(0.0));
+        assert_eq!(LodType::from_resolution(999.0), LodType::Visual(999.0));
+        assert_eq!(LodType::from_resolution(1.0e4), LodType::ShadowVolume(0.0));
+        assert_eq!(LodType::from_resolution(1.001e4), LodType::ShadowVolume(10.0));
+        assert_eq!(LodType::from_resolution(1.1e4), LodType::ShadowBuffer(0.0));
+        assert_eq!(LodType::from_resolution(1.2e4), LodType::Unknown(1.2e4));
+    }
+
+    #[test]
+    fn resolutions_compare_by_type_and_detail() {
+        assert_eq!(Resolution(1.0e15), Resolution(1.0e15 * 1.0005));
+        assert_eq!(Resolution(1.0e13), "1e13".parse::<Resolution>().unwrap());
+        assert_ne!(Resolution(1.0e13), Resolution(2.0e13));
+        assert_ne!(Resolution(1.0e3), Resolution(1.1e3));
+
+        assert_eq!(Resolution(1.0), Resolution(1.0));
+        assert_ne!(Resolution(1.0), Resolution(1.001));
+        assert_ne!(Resolution(1.0e4), Resolution(1.0001e4));
+        // The same level of detail in different kinds of LODs.
+        assert_ne!(Resolution(0.0), Resolution(1.0e4));
+        assert_ne!(Resolution(1.0e4), Resolution(1.1e4));
+
+        // Unknown resolutions only equal themselves.
+        assert_eq!(Resolution(3.0e14), Resolution(3.0e14));
+        assert_ne!(Resolution(3.0e14), Resolution(3.1e14));
+    }
+
+    #[test]
+    fn types_and_groups() {
+        assert!(Resolution(1.1e16).lod_type().is_type("view"));
+        assert!(Resolution(1.9e16).is_shadow());
+        assert!(Resolution(1.1e4).is_shadow());
+        assert!(!Resolution(1.0e13).lod_type().is_type("view"));
+        assert_eq!(Resolution(1.0e4 + 5.0).detail_level(), Some(5.0));
+        assert_eq!(Resolution(1.0e15).detail_level(), None);
+    }
+}
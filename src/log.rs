@@ -0,0 +1,60 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+static PROFILE: AtomicBool = AtomicBool::new(false);
+
+/// Enables the trace of everything read from a file, which is printed to stderr.
+pub fn set_verbose(verbose: bool) {
+    VERBOSE.store(verbose, Ordering::Relaxed);
+}
+
+pub fn verbose() -> bool {
+    VERBOSE.load(Ordering::Relaxed)
+}
+
+/// Enables printing how long every phase of reading and writing takes to stderr.
+pub fn set_profile(profile: bool) {
+    PROFILE.store(profile, Ordering::Relaxed);
+}
+
+pub fn profile() -> bool {
+    PROFILE.load(Ordering::Relaxed)
+}
+
+/// Measures consecutive phases of some work. Unless profiling is enabled, this doesn't read
+/// the clock or build the context.
+pub struct Timer {
+    context: String,
+    last: Option<Instant>,
+}
+
+impl Timer {
+    pub fn start<F: FnOnce() -> String>(context: F) -> Timer {
+        if profile() {
+            Timer { context: context(), last: Some(Instant::now()) }
+        } else {
+            Timer { context: String::new(), last: None }
+        }
+    }
+
+    /// Prints the time since the start or the previous phase.
+    pub fn lap(&mut self, phase: &str) {
+        if let Some(last) = self.last {
+            let now = Instant::now();
+            eprintln!("profile: {:<40} {:>10.3} ms", format!("{} {}", self.context, phase), (now - last).as_secs_f64() * 1000.0);
+            self.last = Some(now);
+        }
+    }
+}
+
+/// Like `eprintln!`, but only prints if verbose output is enabled. The arguments aren't
+/// evaluated otherwise, so they must not read from the file.
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        if $crate::log::verbose() {
+            eprintln!($($arg)*);
+        }
+    };
+}
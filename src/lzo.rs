@@ -0,0 +1,421 @@
+use std::fmt;
+
+// LZO1X decompression, behind a trait so that the implementation can be picked when one of
+// them mishandles a block.
+
+/// Why a block couldn't be decompressed. The search for the size of a block in
+/// `read_compressed_block` relies on telling the first two apart.
+#[derive(Debug)]
+pub enum LzoError {
+    /// The input ended before the end of the block.
+    InputOverrun,
+    /// The block ended before the end of the input.
+    InputNotConsumed,
+    /// Anything else wrong with the block.
+    Other(String),
+}
+
+impl fmt::Display for LzoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LzoError::InputOverrun => write!(f, "Input overrun"),
+            LzoError::InputNotConsumed => write!(f, "Input not consumed"),
+            LzoError::Other(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+pub trait LzoBackend {
+    /// Decompresses the LZO1X block in `input`, which has to end exactly at the end of the
+    /// block, into at most `output_size` bytes.
+    fn decompress(&self, input: &[u8], output_size: usize) -> Result<Vec<u8>, LzoError>;
+}
+
+/// The reference implementation, through the minilzo bindings.
+pub struct MiniLzo;
+
+impl LzoBackend for MiniLzo {
+    fn decompress(&self, input: &[u8], output_size: usize) -> Result<Vec<u8>, LzoError> {
+        minilzo::decompress(input, output_size).map_err(|e| match e {
+            minilzo::Error::InputOverrun => LzoError::InputOverrun,
+            minilzo::Error::InputNotConsumed => LzoError::InputNotConsumed,
+            e => LzoError::Other(format!("{:?}", e)),
+        })
+    }
+}
+
+/// An LZO1X decoder written after lzokay rather than minilzo, so that the two backends are
+/// separate implementations and a block one of them mishandles can be read with the other.
+/// The only stream it decodes differently is one that ends in an M4 match of distance 0
+/// other than `0x11 0 0`: minilzo takes any such match as the end of the block, this only
+/// the end marker that LZO1X compressors write.
+pub struct RustLzo;
+
+struct Decoder<'a> {
+    input: &'a [u8],
+    ip: usize,
+    output: Vec<u8>,
+    /// Number of bytes decompressed so far, which are only kept in `output` if `write` is set.
+    len: usize,
+    write: bool,
+    output_size: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn byte(&mut self) -> Result<usize, LzoError> {
+        let b = *self.input.get(self.ip).ok_or(LzoError::InputOverrun)?;
+        self.ip += 1;
+        Ok(b as usize)
+    }
+
+    /// Reads the 16-bit little-endian word that follows M3 and M4 instructions.
+    fn word(&mut self) -> Result<usize, LzoError> {
+        Ok(self.byte()? | (self.byte()? << 8))
+    }
+
+    /// Reads the rest of a length whose bits in the instruction are all 0. Every zero byte
+    /// adds 255, the first other byte ends the length.
+    fn extended_length(&mut self, base: usize) -> Result<usize, LzoError> {
+        let mut zeros = 0;
+        loop {
+            let b = self.byte()?;
+            if b != 0 {
+                return Ok(zeros * 255 + base + b);
+            }
+            zeros += 1;
+        }
+    }
+
+    fn literals(&mut self, n: usize) -> Result<(), LzoError> {
+        let end = self.ip.checked_add(n).filter(|end| *end <= self.input.len()).ok_or(LzoError::InputOverrun)?;
+        if self.len + n > self.output_size {
+            return Err(LzoError::Other("Output overrun".to_string()));
+        }
+        if self.write {
+            self.output.extend_from_slice(&self.input[self.ip..end]);
+        }
+        self.len += n;
+        self.ip = end;
+        Ok(())
+    }
+
+    /// Copies `n` bytes starting `distance` bytes back. The ranges may overlap, so the bytes
+    /// are copied one at a time.
+    fn copy_match(&mut self, distance: usize, n: usize) -> Result<(), LzoError> {
+        if distance > self.len {
+            return Err(LzoError::Other("Lookbehind overrun".to_string()));
+        }
+        if self.len + n > self.output_size {
+            return Err(LzoError::Other("Output overrun".to_string()));
+        }
+        if self.write {
+            let start = self.len - distance;
+            for i in 0..n {
+                let b = self.output[start + i];
+                self.output.push(b);
+            }
+        }
+        self.len += n;
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Vec<u8>, LzoError> {
+        if self.ip < self.input.len() {
+            Err(LzoError::InputNotConsumed)
+        } else {
+            Ok(self.output)
+        }
+    }
+
+    /// Decodes instructions until the end of the block.
+    fn run(&mut self) -> Result<(), LzoError> {
+        // Number of literals after the last instruction, or 4 after a literal run of any
+        // length. Instructions below 16 are a literal run after none, and a short match with
+        // a different length and distance after few or many.
+        let mut state = 0;
+
+        match self.input.first().map(|b| *b as usize) {
+            Some(b) if b >= 22 => {
+                self.ip += 1;
+                self.literals(b - 17)?;
+                state = 4;
+            },
+            Some(b) if b >= 18 => {
+                self.ip += 1;
+                state = b - 17;
+                self.literals(state)?;
+            },
+            _ => {},
+        }
+
+        loop {
+            let inst = self.byte()?;
+
+            // Distance and length of the match, and the number of literals following it.
+            let (distance, len, next_state) = if inst >= 64 {
+                let distance = ((inst >> 2) & 7) + (self.byte()? << 3) + 1;
+                (distance, (inst >> 5) + 1, inst & 3)
+            } else if inst >= 32 {
+                let len = match inst & 31 {
+                    0 => self.extended_length(31)?,
+                    n => n,
+                } + 2;
+                let word = self.word()?;
+                ((word >> 2) + 1, len, word & 3)
+            } else if inst >= 16 {
+                let len = match inst & 7 {
+                    0 => self.extended_length(7)?,
+                    n => n,
+                } + 2;
+                let word = self.word()?;
+                let distance = ((inst & 8) << 11) + (word >> 2);
+                if distance == 0 {
+                    if len != 3 {
+                        return Err(LzoError::Other("Malformed end of block".to_string()));
+                    }
+                    return Ok(());
+                }
+                (distance + 0x4000, len, word & 3)
+            } else if state == 0 {
+                let len = match inst {
+                    0 => self.extended_length(15)?,
+                    n => n,
+                } + 3;
+                self.literals(len)?;
+                state = 4;
+                continue;
+            } else if state < 4 {
+                ((inst >> 2) + (self.byte()? << 2) + 1, 2, inst & 3)
+            } else {
+                ((inst >> 2) + (self.byte()? << 2) + 2049, 3, inst & 3)
+            };
+
+            self.copy_match(distance, len)?;
+            self.literals(next_state)?;
+            state = next_state;
+        }
+    }
+}
+
+impl LzoBackend for RustLzo {
+    fn decompress(&self, input: &[u8], output_size: usize) -> Result<Vec<u8>, LzoError> {
+        let mut decoder = Decoder {
+            input,
+            ip: 0,
+            output: Vec::with_capacity(output_size),
+            len: 0,
+            write: true,
+            output_size,
+        };
+        decoder.run()?;
+        decoder.finish()
+    }
+}
+
+/// Walks through the LZO1X block at the start of `input` without writing anything, and returns
+/// how many bytes of `input` it takes up. The size of a block is only known from the marker at
+/// its end, so this is the cheapest way to find it. Fails unless the block decompresses to
+/// exactly `output_size` bytes.
+pub fn compressed_size(input: &[u8], output_size: usize) -> Result<usize, LzoError> {
+    let mut decoder = Decoder {
+        input,
+        ip: 0,
+        output: Vec::new(),
+        len: 0,
+        write: false,
+        output_size,
+    };
+    decoder.run()?;
+
+    if decoder.len != output_size {
+        return Err(LzoError::Other(format!("Block decompresses to {} bytes, expected {}", decoder.len, output_size)));
+    }
+    Ok(decoder.ip)
+}
+
+/// The available backends, for picking one in `ReadOptions`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Lzo {
+    #[default]
+    MiniLzo,
+    Rust,
+}
+
+pub const LZO_BACKENDS: &[(&str, Lzo)] = &[
+    ("minilzo", Lzo::MiniLzo),
+    ("rust", Lzo::Rust),
+];
+
+impl Lzo {
+    pub fn from_name(name: &str) -> Option<Lzo> {
+        let name = name.to_lowercase();
+        LZO_BACKENDS.iter().find(|(n, _)| *n == name).map(|(_, l)| *l)
+    }
+
+    pub fn name(&self) -> &'static str {
+        LZO_BACKENDS.iter().find(|(_, l)| l == self).map(|(n, _)| *n).unwrap()
+    }
+
+    pub fn backend(&self) -> &'static dyn LzoBackend {
+        match self {
+            Lzo::MiniLzo => &MiniLzo,
+            Lzo::Rust => &RustLzo,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What a backend made of a block, with errors other than the two overruns lumped
+    /// together since the messages differ.
+    #[derive(Debug, PartialEq)]
+    enum Outcome {
+        Ok(Vec<u8>),
+        InputOverrun,
+        InputNotConsumed,
+        Other,
+    }
+
+    fn outcome(result: Result<Vec<u8>, LzoError>) -> Outcome {
+        match result {
+            Ok(output) => Outcome::Ok(output),
+            Err(LzoError::InputOverrun) => Outcome::InputOverrun,
+            Err(LzoError::InputNotConsumed) => Outcome::InputNotConsumed,
+            Err(LzoError::Other(_)) => Outcome::Other,
+        }
+    }
+
+    /// Decompresses with both backends, which have to agree.
+    fn decompress(input: &[u8], output_size: usize) -> Outcome {
+        let rust = outcome(RustLzo.decompress(input, output_size));
+        assert_eq!(rust, outcome(MiniLzo.decompress(input, output_size)), "input {:?}", input);
+        rust
+    }
+
+    /// A block with every kind of match and literal run, and what it decompresses to.
+    fn every_match_kind() -> (Vec<u8>, Vec<u8>) {
+        let mut input: Vec<u8> = Vec::new();
+        let mut expected: Vec<u8> = Vec::new();
+
+        // A first literal run of 4 bytes, then 3 bytes from 4 back.
+        input.push(17 + 4);
+        input.extend_from_slice(b"abcd");
+        input.extend_from_slice(&[(2 << 5) | (3 << 2), 0]);
+        expected.extend_from_slice(b"abcdabc");
+
+        // 20 literals with the length in an extra byte.
+        input.extend_from_slice(&[0, 2]);
+        input.extend_from_slice(b"ABCDEFGHIJKLMNOPQRST");
+        expected.extend_from_slice(b"ABCDEFGHIJKLMNOPQRST");
+
+        // 2074 bytes from 1 back with the length continued over zeros, then 2 literals.
+        input.push(32);
+        input.extend_from_slice(&[0; 8]);
+        input.extend_from_slice(&[1, 2, 0]);
+        input.extend_from_slice(b"xy");
+        expected.extend_from_slice(&[b'T'; 2074]);
+        expected.extend_from_slice(b"xy");
+
+        // A short match of 2 bytes from 2 back, following the literals after a match.
+        input.extend_from_slice(&[1 << 2, 0]);
+        expected.extend_from_slice(b"xy");
+
+        // 4 literals, then the short match of 3 bytes from 2049 back only allowed after them.
+        input.push(1);
+        input.extend_from_slice(b"wxyz");
+        input.extend_from_slice(&[0, 0]);
+        expected.extend_from_slice(b"wxyz");
+        let start = expected.len() - 2049;
+        expected.extend_from_within(start..start + 3);
+
+        // 14379 bytes from 1 back, to get far enough for a match from more than 16K back.
+        input.push(32);
+        input.extend_from_slice(&[0; 56]);
+        input.extend_from_slice(&[66, 0, 0]);
+        let last = *expected.last().unwrap();
+        expected.resize(expected.len() + 14379, last);
+
+        // 5 bytes from 16484 back, which is "ABCDE".
+        let distance: u16 = 100 << 2;
+        input.extend_from_slice(&[16 | 3, distance as u8, (distance >> 8) as u8]);
+        let start = expected.len() - 16484;
+        expected.extend_from_within(start..start + 5);
+        assert_eq!(&expected[expected.len() - 5..], b"ABCDE");
+
+        // End of the block.
+        input.extend_from_slice(&[0x11, 0, 0]);
+
+        (input, expected)
+    }
+
+    #[test]
+    fn match_kinds() {
+        let (input, expected) = every_match_kind();
+        assert_eq!(decompress(&input, expected.len()), Outcome::Ok(expected.clone()));
+        assert_eq!(compressed_size(&input, expected.len()).unwrap(), input.len());
+
+        // A first literal run of fewer than 4 bytes is followed by a match right away.
+        let input = [17 + 2, b'h', b'i', (2 << 5) | (1 << 2), 0, 0x11, 0, 0];
+        assert_eq!(decompress(&input, 5), Outcome::Ok(b"hihih".to_vec()));
+    }
+
+    #[test]
+    fn truncated_input() {
+        let (input, expected) = every_match_kind();
+        for len in 0..input.len() {
+            assert_eq!(decompress(&input[..len], expected.len()), Outcome::InputOverrun, "truncated to {} bytes", len);
+        }
+    }
+
+    #[test]
+    fn trailing_bytes() {
+        let (mut input, expected) = every_match_kind();
+        let size = input.len();
+        input.extend_from_slice(&[0x11, 0, 0, 0xff]);
+        assert_eq!(decompress(&input, expected.len()), Outcome::InputNotConsumed);
+        assert_eq!(compressed_size(&input, expected.len()).unwrap(), size);
+    }
+
+    #[test]
+    fn bad_blocks() {
+        // A match from further back than was decompressed so far.
+        let input = [17 + 4, b'a', b'b', b'c', b'd', (2 << 5) | (7 << 2), 0, 0x11, 0, 0];
+        assert_eq!(decompress(&input, 7), Outcome::Other);
+        // More output than there is room for.
+        let (input, expected) = every_match_kind();
+        assert_eq!(decompress(&input, expected.len() - 1), Outcome::Other);
+        assert!(compressed_size(&input, expected.len() + 1).is_err());
+    }
+
+    #[test]
+    fn end_markers() {
+        // minilzo ends the block at any M4 match with a distance of 0, RustLzo only at the
+        // end marker compressors write, with a length of 3.
+        for marker in [&[0x12, 0, 0][..], &[0x10, 1, 0, 0][..]].iter() {
+            let mut input = vec![17 + 4, b'a', b'b', b'c', b'd'];
+            input.extend_from_slice(marker);
+            assert_eq!(outcome(MiniLzo.decompress(&input, 4)), Outcome::Ok(b"abcd".to_vec()));
+            assert_eq!(outcome(RustLzo.decompress(&input, 4)), Outcome::Other);
+            assert!(compressed_size(&input, 4).is_err());
+        }
+    }
+
+    #[test]
+    fn compressed_by_minilzo() {
+        let text: Vec<u8> = b"class CfgModels { class Default; };\n".iter().cycle().take(50_000).cloned().collect();
+        let mut noise: Vec<u8> = Vec::with_capacity(50_000);
+        let mut x: u32 = 1;
+        for _ in 0..50_000 {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            noise.push((x >> 24) as u8 & 0x0f);
+        }
+
+        for data in [vec![0; 100_000], text, noise, b"abc".to_vec()].iter() {
+            let compressed = minilzo::compress(data).unwrap();
+            assert_eq!(decompress(&compressed, data.len()), Outcome::Ok(data.clone()));
+            assert_eq!(compressed_size(&compressed, data.len()).unwrap(), compressed.len());
+        }
+    }
+}
@@ -1,731 +1,1584 @@
+#[cfg(feature = "wasm")]
+compile_error!("The command line tool needs the filesystem, build only the library with the wasm feature.");
+
+use std::collections::{BTreeSet, HashMap};
 use std::fs::File;
-use std::io::{BufReader, Cursor, Error, ErrorKind, Read, Seek, SeekFrom};
-use std::path::PathBuf;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
 
-use byteorder::{LittleEndian, ReadBytesExt};
 use linked_hash_map::LinkedHashMap;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use docopt::Docopt;
-use armake2::p3d::{P3D, LOD, Face, Vertex, Point};
-
-mod io;
-use crate::io::ReadExt;
+use armake2::p3d::{LOD, Face, Vertex, Point};
+
+use crowbar::compare::Comparison;
+use crowbar::dump::GeometryDump;
+use crowbar::export::{self, ExportOptions, Format, Normals};
+use crowbar::io::{to_hex, HashWriter, Offset};
+use crowbar::lod::{LodType, Resolution, LOD_TYPE_GROUPS};
+use crowbar::log::Timer;
+use crowbar::lzo::{Lzo, LZO_BACKENDS};
+use crowbar::mlod::{convert_streaming, write_mlod};
+use crowbar::modelcfg::write_model_cfg;
+use crowbar::odol::{count_faces, open_input, parse_header_with_options, read_odol, read_paths, LodInfo, OdolHeader, ParsedModel, ReadOptions, Selection, MLOD_VERSIONS};
+use crowbar::stats::Stats;
 
 pub const USAGE: &'static str = "
 crowbar
 
 Usage:
-    crowbar <input> [<output>]
+    crowbar <command> [<args>...]
     crowbar (-h | --help)
     crowbar --version
 
+Commands:
+    convert     Convert an ODOL P3D to MLOD.
+    info        Print information about an ODOL P3D.
+    export      Export the geometry of an ODOL P3D to another format.
+
 Options:
     -h --help                   Show usage information and exit.
        --version                Print the version number and exit.
+
+Run 'crowbar <command> --help' for the options of a command. Without a command,
+'crowbar <input> [<output>]' is the same as 'crowbar convert <input> [<output>]'.
 ";
+
+const USAGE_CONVERT: &'static str = "
+Convert an ODOL P3D to MLOD.
+
+Usage:
+    crowbar convert [options] [--assert-version <v>]... --pbo-out <pbo> <inputs>...
+    crowbar convert [options] [--assert-version <v>]... <input> [<output>]
+    crowbar convert (-h | --help)
+
+Options:
+    -s --select <name>          Only output the points and faces of the named selection.
+       --lowercase-paths        Lowercase all texture, material and proxy paths.
+       --keep-going             Warn about LODs that fail to parse instead of aborting.
+       --strict                 Fail on anything unexpected that is otherwise only
+                                warned about or skipped, like keyframes or sections
+                                outside of the faces.
+       --sanitize-floats        Replace NaN and infinite coordinates, UVs and bounding
+                                data with 0, and warn about how many there were.
+       --timeout <seconds>      Give up on an input after reading it took this long,
+                                e.g. for blocks whose size can't be found.
+    -v --verbose                Print everything read from the file to stderr.
+       --profile                Print how long reading every LOD and writing took to
+                                stderr.
+       --debug-offsets          On a parse error, print the file offset and a hex dump
+                                of the surrounding bytes.
+       --skip-bytes <n>         Start reading the model after n bytes, for files with
+                                an extra header. [default: 0]
+       --magic <magic>          Expect a different four-character magic than ODOL.
+                                [default: ODOL]
+       --max-lods <n>           Treat models with more LODs as corrupt. [default: 64]
+       --lzo-backend <name>     Decompress LZO arrays with minilzo or rust, a separate
+                                decoder in Rust, for blocks one of them can't read.
+                                [default: minilzo]
+       --assert-version <v>     Fail unless the ODOL version is v, before reading
+                                anything else. Can be given several times to allow
+                                several versions.
+       --no-bounding-offset     Keep points in ODOL-local coordinates instead of moving
+                                them by the bounding center.
+       --passthrough-mlod       Copy inputs that already are MLOD to the output unchanged.
+       --mlod-version <n>       Version of the written MLOD. [default: 257]
+       --strip-selections       Don't write named selections, including proxies.
+       --strip-properties       Don't write named properties.
+       --skip-empty-lods        Don't write LODs without faces, like the memory LOD.
+       --only-type <type>       Only write the LODs of a type like geometry or memory,
+                                or of all view or shadow types.
+       --repair                 Drop whatever references missing data and keep LODs as
+                                far as they can be read, then list what was dropped.
+                                The output may be incomplete.
+       --faithful               Keep the vertex order of faces as in the ODOL instead
+                                of reversing it to the MLOD winding. Binarizing the
+                                output gives back the same vertex order, but faces
+                                point inwards in Object Builder.
+    -t --output-template <t>    Build the output path from the input path instead of
+                                passing <output>. {dir}, {stem} and {ext} expand to the
+                                directory, file name without extension and extension
+                                of the input, e.g. {dir}/{stem}_mlod.{ext}
+       --low-memory             Write every LOD as soon as it is read instead of reading
+                                the whole model first, for models too large to fit
+                                into memory. Needs an output and doesn't print the
+                                summary of the model.
+       --dump-model-cfg <file>  Also write the skeleton and animations of the model
+                                to a model.cfg.
+       --print-hash             Print the SHA-256 of the written MLOD, like sha256sum.
+                                Without an output, the MLOD is only hashed. Only the
+                                hash is printed, not the summary of the model.
+       --out-format <format>    Write mlod, json like info --json, or any format of
+                                export with all LODs. Picked by the extension of the
+                                output if left out, mlod for unknown extensions.
+       --pbo-out <pbo>          Convert all inputs and pack the MLODs into a new PBO,
+                                named by the paths of the inputs relative to the
+                                working directory. Inputs that would get the same
+                                name are an error.
+       --manifest <file>        Also write a JSON file listing every input with whether
+                                it was converted and written, or the error if not.
+    -h --help                   Show usage information and exit.
+";
+
+const USAGE_INFO: &'static str = "
+Print information about an ODOL P3D.
+
+Usage:
+    crowbar info [options] [--assert-version <v>]... <input>...
+    crowbar info (-h | --help)
+
+Options:
+       --json                   Print the information as JSON, on a single line.
+       --json-pretty            Like --json, but indented. Also applies to --manifest.
+       --stats                  Print aggregate geometry statistics.
+       --compression            Print how every array is stored in the file.
+       --selection-report       Print the named selections of every LOD with their
+                                number of faces and points.
+       --dump-geometry          Print every point and face, with the vertex point,
+                                normal and UV of every face, like Object Builder
+                                lists them.
+       --texture-list           Only print the texture paths, one per line.
+       --material-list          Only print the material paths, one per line.
+                                These two accept multiple inputs and list the paths
+                                used by any of them.
+       --validate-only          Only check the inputs for violated assumptions about the
+                                format and print them. Accepts multiple inputs.
+       --count-only             Only print the number of LODs and faces of every input
+                                as CSV. Accepts multiple inputs.
+       --list-lods              Only print the index, resolution and type of every LOD,
+                                separated by tabs. Only the header is read.
+       --compare                Compare two inputs and print how their LODs,
+                                selections and materials differ. Exits with 1 if they
+                                do.
+       --manifest <file>        Only write the version, LODs and paths of every input
+                                to a JSON file, or the error if it can't be read.
+                                Accepts multiple inputs.
+       --keep-going             Warn about LODs that fail to parse instead of aborting.
+       --strict                 Fail on anything unexpected that is otherwise only
+                                warned about or skipped, like keyframes or sections
+                                outside of the faces.
+       --sanitize-floats        Replace NaN and infinite coordinates, UVs and bounding
+                                data with 0, and warn about how many there were.
+       --timeout <seconds>      Give up on an input after reading it took this long,
+                                e.g. for blocks whose size can't be found.
+    -v --verbose                Print everything read from the file to stderr.
+       --debug-offsets          On a parse error, print the file offset and a hex dump
+                                of the surrounding bytes.
+       --skip-bytes <n>         Start reading the model after n bytes, for files with
+                                an extra header. [default: 0]
+       --magic <magic>          Expect a different four-character magic than ODOL.
+                                [default: ODOL]
+       --max-lods <n>           Treat models with more LODs as corrupt. [default: 64]
+       --lzo-backend <name>     Decompress LZO arrays with minilzo or rust, a separate
+                                decoder in Rust, for blocks one of them can't read.
+                                [default: minilzo]
+       --assert-version <v>     Fail unless the ODOL version is v, before reading
+                                anything else. Can be given several times to allow
+                                several versions. Also checked by the path lists,
+                                counts and --list-lods.
+    -h --help                   Show usage information and exit.
+";
+
+const USAGE_EXPORT: &'static str = "
+Export the geometry of an ODOL P3D to another format.
+
+Usage:
+    crowbar export [options] [--assert-version <v>]... <input> <output>
+    crowbar export (-h | --help)
+
+Options:
+    -f --format <format>        Output format: obj, gltf, glb, stl, ply or dae. gltf
+                                with a .glb output writes glb. [default: obj]
+    -l --lod <lods>             Comma-separated resolutions or LOD types (e.g. 1.0,geometry)
+                                to export. Defaults to the first LOD.
+       --lod-index <indices>    Comma-separated positions of LODs in the file to export,
+                                in addition to those picked with --lod.
+       --only-type <type>       Export the LODs of a type like geometry or memory, or of
+                                all view or shadow types, in addition to those picked
+                                with --lod.
+       --merge-lods             Export all visual LODs into one file, each as a separate
+                                object. Use --lod to pick other LODs instead.
+       --split                  Export every LOD to a separate file, named after the
+                                output with the LOD appended, e.g. model_geometry.obj.
+                                Exports all LODs unless picked with --lod.
+       --first-lod-only         Only read the visual LOD with the most detail, which is
+                                faster than reading all and picking one with --lod.
+       --include-empty-lods     Also export LODs without faces, which are skipped by
+                                default.
+       --flip-winding           Reverse the vertex order of faces, for tools that show
+                                the exported normals inverted.
+       --sort-faces-by-material
+                                Group faces by their material, so that importers
+                                don't have to switch materials as often.
+       --flip-uv-v              Write V as 1 - V. UVs have their origin at the top left
+                                like in Arma and glTF, OBJ and DAE importers usually
+                                expect it at the bottom left.
+       --facet-normals          Write a normal per triangle, so faces look flat. This
+                                is the default for dae and stl, the other formats
+                                get no normals by default. Not supported by ply.
+       --smooth-normals         Write a normal per point, averaged from the triangles
+                                around it. Not supported by stl.
+    -s --select <name>          Only output the points and faces of the named selection.
+       --lowercase-paths        Lowercase all texture, material and proxy paths.
+       --keep-going             Warn about LODs that fail to parse instead of aborting.
+       --strict                 Fail on anything unexpected that is otherwise only
+                                warned about or skipped, like keyframes or sections
+                                outside of the faces.
+       --sanitize-floats        Replace NaN and infinite coordinates, UVs and bounding
+                                data with 0, and warn about how many there were.
+       --timeout <seconds>      Give up on an input after reading it took this long,
+                                e.g. for blocks whose size can't be found.
+    -v --verbose                Print everything read from the file to stderr.
+       --profile                Print how long reading every LOD and writing took to
+                                stderr.
+       --debug-offsets          On a parse error, print the file offset and a hex dump
+                                of the surrounding bytes.
+       --skip-bytes <n>         Start reading the model after n bytes, for files with
+                                an extra header. [default: 0]
+       --magic <magic>          Expect a different four-character magic than ODOL.
+                                [default: ODOL]
+       --max-lods <n>           Treat models with more LODs as corrupt. [default: 64]
+       --lzo-backend <name>     Decompress LZO arrays with minilzo or rust, a separate
+                                decoder in Rust, for blocks one of them can't read.
+                                [default: minilzo]
+       --assert-version <v>     Fail unless the ODOL version is v, before reading
+                                anything else. Can be given several times to allow
+                                several versions.
+       --no-bounding-offset     Keep points in ODOL-local coordinates instead of moving
+                                them by the bounding center.
+    -h --help                   Show usage information and exit.
+";
+
 const VERSION: &'static str = env!("CARGO_PKG_VERSION");
 
+const COMMANDS: &[&str] = &["convert", "info", "export"];
+
 #[derive(Debug, Deserialize)]
 struct Args {
     flag_version: bool,
+    arg_command: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ConvertArgs {
+    flag_select: Option<String>,
+    flag_lowercase_paths: bool,
+    flag_keep_going: bool,
+    flag_strict: bool,
+    flag_sanitize_floats: bool,
+    flag_timeout: Option<f64>,
+    flag_verbose: bool,
+    flag_profile: bool,
+    flag_debug_offsets: bool,
+    flag_skip_bytes: u64,
+    flag_magic: String,
+    flag_max_lods: u32,
+    flag_lzo_backend: String,
+    flag_assert_version: Vec<u32>,
+    flag_no_bounding_offset: bool,
+    flag_passthrough_mlod: bool,
+    flag_mlod_version: u32,
+    flag_strip_selections: bool,
+    flag_strip_properties: bool,
+    flag_skip_empty_lods: bool,
+    flag_only_type: Option<String>,
+    flag_repair: bool,
+    flag_faithful: bool,
+    flag_output_template: Option<String>,
+    flag_low_memory: bool,
+    flag_dump_model_cfg: Option<PathBuf>,
+    flag_print_hash: bool,
+    flag_out_format: Option<String>,
+    flag_pbo_out: Option<PathBuf>,
+    flag_manifest: Option<PathBuf>,
     arg_input: PathBuf,
     arg_output: Option<PathBuf>,
+    arg_inputs: Vec<PathBuf>,
+}
+
+#[derive(Debug, Deserialize)]
+struct InfoArgs {
+    flag_json: bool,
+    flag_json_pretty: bool,
+    flag_stats: bool,
+    flag_compression: bool,
+    flag_selection_report: bool,
+    flag_dump_geometry: bool,
+    flag_keep_going: bool,
+    flag_strict: bool,
+    flag_sanitize_floats: bool,
+    flag_timeout: Option<f64>,
+    flag_verbose: bool,
+    flag_debug_offsets: bool,
+    flag_skip_bytes: u64,
+    flag_magic: String,
+    flag_max_lods: u32,
+    flag_lzo_backend: String,
+    flag_assert_version: Vec<u32>,
+    flag_texture_list: bool,
+    flag_material_list: bool,
+    flag_validate_only: bool,
+    flag_count_only: bool,
+    flag_list_lods: bool,
+    flag_compare: bool,
+    flag_manifest: Option<PathBuf>,
+    arg_input: Vec<PathBuf>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExportArgs {
+    flag_format: String,
+    flag_lod: Option<String>,
+    flag_lod_index: Option<String>,
+    flag_only_type: Option<String>,
+    flag_merge_lods: bool,
+    flag_split: bool,
+    flag_first_lod_only: bool,
+    flag_include_empty_lods: bool,
+    flag_flip_winding: bool,
+    flag_flip_uv_v: bool,
+    flag_facet_normals: bool,
+    flag_smooth_normals: bool,
+    flag_sort_faces_by_material: bool,
+    flag_select: Option<String>,
+    flag_lowercase_paths: bool,
+    flag_keep_going: bool,
+    flag_strict: bool,
+    flag_sanitize_floats: bool,
+    flag_timeout: Option<f64>,
+    flag_verbose: bool,
+    flag_profile: bool,
+    flag_debug_offsets: bool,
+    flag_skip_bytes: u64,
+    flag_magic: String,
+    flag_max_lods: u32,
+    flag_lzo_backend: String,
+    flag_assert_version: Vec<u32>,
+    flag_no_bounding_offset: bool,
+    arg_input: PathBuf,
+    arg_output: PathBuf,
 }
 
-fn read_compressed_array<I: Read + Seek>(reader: &mut I, output_size: usize) -> Result<Vec<u8>, Error> {
-    let comp_type = reader.read_u8()?;
-    if comp_type == 0 {
-        let mut buffer = Vec::with_capacity(output_size);
-        buffer.resize(output_size, 0);
-        reader.read_exact(&mut buffer)?;
-        return Ok(buffer);
+/// Returns the points and faces of the named selection as a LOD of their own, or None if the
+/// LOD has no such selection. Fails if the selection doesn't fit the points and faces.
+fn extract_selection(lod: &LOD, info: &LodInfo, name: &str) -> Result<Option<(LOD, LodInfo)>, String> {
+    let num_points = lod.points.len();
+    let num_faces = lod.faces.len();
+
+    let mask = match lod.taggs.get(name) {
+        Some(mask) => mask,
+        None => return Ok(None),
+    };
+    if mask.len() != num_points + num_faces {
+        return Err(format!("Selection \"{}\" of LOD {} has {} entries, but the LOD has {} points and {} faces.",
+            name, LodType::from_resolution(lod.resolution), mask.len(), num_points, num_faces));
     }
 
-    assert_eq!(comp_type, 2);
+    let (mask_points, mask_faces) = mask.split_at(num_points);
 
-    let fp = reader.seek(SeekFrom::Current(0))?;
-    let mut size_small = 0;
-    let mut size_large = output_size;
+    let mut point_map: Vec<Option<u32>> = Vec::with_capacity(num_points);
+    let mut num_selected = 0;
+    for w in mask_points {
+        if *w > 0 {
+            point_map.push(Some(num_selected));
+            num_selected += 1;
+        } else {
+            point_map.push(None);
+        }
+    }
 
-    // TODO: super hacky
-    loop {
-        if size_large < size_small || size_large - size_small <= 1 {
-            return Err(Error::new(ErrorKind::Other, ""));
+    let mut sub = LOD {
+        version_major: lod.version_major,
+        version_minor: lod.version_minor,
+        resolution: lod.resolution,
+        points: Vec::new(),
+        face_normals: Vec::new(),
+        faces: Vec::new(),
+        taggs: LinkedHashMap::new(),
+    };
+
+    for (i, point) in lod.points.iter().enumerate() {
+        if point_map[i].is_some() {
+            sub.points.push(Point { coords: point.coords, flags: point.flags });
+            sub.face_normals.push(lod.face_normals[i]);
         }
+    }
 
-        let size = size_small + (size_large - size_small) / 2;
-        println!("    guessing LZO size: {:?} ({} - {})", size, size_small, size_large);
+    let mut face_map: Vec<bool> = Vec::with_capacity(num_faces);
+    for (face, selected) in lod.faces.iter().zip(mask_faces.iter()) {
+        let keep = *selected > 0 && face.vertices.iter().all(|v| point_map[v.point_index as usize].is_some());
+        face_map.push(keep);
+        if !keep {
+            continue;
+        }
 
-        //let mut buffer = [0; size];
-        let mut buffer = Vec::with_capacity(size);
-        buffer.resize(size, 0);
+        let vertices: Vec<Vertex> = face.vertices.iter().map(|v| Vertex {
+            point_index: point_map[v.point_index as usize].unwrap(),
+            normal_index: point_map[v.normal_index as usize].unwrap(),
+            uv: v.uv,
+        }).collect();
+
+        sub.faces.push(Face {
+            vertices,
+            flags: face.flags,
+            texture: face.texture.clone(),
+            material: face.material.clone()
+        });
+    }
 
-        reader.seek(SeekFrom::Start(fp))?;
-        let result = reader.read_exact(&mut buffer);
+    let mut face_indices: Vec<Option<u32>> = Vec::with_capacity(num_faces);
+    let mut num_kept = 0;
+    for keep in face_map.iter() {
+        face_indices.push(if *keep { Some(num_kept) } else { None });
+        num_kept += *keep as u32;
+    }
 
-        if let Err(e) = result {
-            if e.kind() == ErrorKind::UnexpectedEof {
-                size_large = size;
-                continue;
-            } else {
-                return Err(e);
-            }
+    for (tagg_name, tagg) in lod.taggs.iter() {
+        if tagg.len() != num_points + num_faces {
+            continue;
         }
 
-        let result = minilzo::decompress(&buffer, output_size);
-        match result {
-            Ok(decomp) => {
-                return Ok(decomp);
-            },
-            Err(minilzo::Error::InputOverrun) => {
-                size_small = size;
-            },
-            Err(minilzo::Error::InputNotConsumed) => {
-                size_large = size;
-            },
-            Err(e) => {
-                return Err(Error::new(ErrorKind::Other, format!("{:?}", e)));
+        let (tagg_points, tagg_faces) = tagg.split_at(num_points);
+        let mut sub_tagg: Vec<u8> = Vec::with_capacity(sub.points.len() + sub.faces.len());
+        sub_tagg.extend(tagg_points.iter().zip(point_map.iter()).filter(|(_, m)| m.is_some()).map(|(w, _)| *w));
+        sub_tagg.extend(tagg_faces.iter().zip(face_map.iter()).filter(|(_, m)| **m).map(|(w, _)| *w));
+        sub.taggs.insert(tagg_name.clone(), sub_tagg.into_boxed_slice());
+    }
+
+    let sub_info = LodInfo {
+        uv_sets: info.uv_sets.iter().map(|uv_set| {
+            uv_set.iter().zip(point_map.iter()).filter(|(_, m)| m.is_some()).map(|(uv, _)| *uv).collect()
+        }).collect(),
+        selections: info.selections.iter().map(|selection| {
+            let (points, weights) = selection.points.iter().zip(selection.weights.iter())
+                .filter_map(|(i, w)| point_map[*i as usize].map(|i| (i, *w)))
+                .unzip();
+            Selection {
+                name: selection.name.clone(),
+                points,
+                weights,
+                faces: selection.faces.iter().filter_map(|i| face_indices[*i as usize]).collect(),
             }
+        }).collect(),
+        ..info.clone()
+    };
+
+    Ok(Some((sub, sub_info)))
+}
+
+fn lowercase_paths(lod: &mut LOD, info: &mut LodInfo) {
+    for face in lod.faces.iter_mut() {
+        face.texture = face.texture.to_lowercase();
+        face.material = face.material.to_lowercase();
+    }
+
+    for material in info.materials.iter_mut() {
+        material.path = material.path.to_lowercase();
+        for stage in material.stages.iter_mut() {
+            stage.texture = stage.texture.to_lowercase();
+        }
+    }
+
+    let taggs = std::mem::replace(&mut lod.taggs, LinkedHashMap::new());
+    for (name, tagg) in taggs {
+        if name.starts_with("proxy:") {
+            lod.taggs.insert(name.to_lowercase(), tagg);
+        } else {
+            lod.taggs.insert(name, tagg);
+        }
+    }
+
+    for selection in info.selections.iter_mut() {
+        if selection.name.starts_with("proxy:") {
+            selection.name = selection.name.to_lowercase();
         }
     }
 }
 
-fn read_odol(path: PathBuf) -> Result<P3D, Error> {
-    let mut reader = BufReader::new(File::open(path)?);
+fn read_magic(path: &Path) -> std::io::Result<[u8; 4]> {
+    let mut magic = [0; 4];
+    File::open(path)?.read_exact(&mut magic)?;
+    Ok(magic)
+}
 
-    let mut buffer = [0; 4];
-    reader.read_exact(&mut buffer)?;
-    assert_eq!(&buffer, b"ODOL");
+fn lod_matches(lod: &LOD, spec: &str) -> bool {
+    let resolution = Resolution(lod.resolution);
+    match spec.parse::<Resolution>() {
+        Ok(spec) => spec == resolution,
+        Err(_) => resolution.lod_type().name() == spec,
+    }
+}
 
-    let version = reader.read_u32::<LittleEndian>()?;
-    println!("version: {}", version);
+fn check_lod_type(name: &str) -> Result<(), String> {
+    if !LodType::names().contains(&name) && !LOD_TYPE_GROUPS.contains(&name) {
+        let mut names = LodType::names();
+        names.extend_from_slice(LOD_TYPE_GROUPS);
+        return Err(format!("Unknown LOD type \"{}\", expected one of: {}.", name, names.join(", ")));
+    }
+    Ok(())
+}
 
-    assert!(version == 71 || version == 73);
+/// Keeps only the LODs for which `keep` returns true, given their index and the LOD.
+fn retain_lods<F: Fn(usize, &LOD) -> bool>(model: &mut ParsedModel, keep: F) {
+    let lods = std::mem::take(&mut model.mlod.lods);
+    let infos = std::mem::take(&mut model.lods);
+    let (lods, infos) = lods.into_iter().zip(infos)
+        .enumerate()
+        .filter(|(i, (lod, _))| keep(*i, lod))
+        .map(|(_, pair)| pair)
+        .unzip();
+    model.mlod.lods = lods;
+    model.lods = infos;
+}
 
-    let appid = reader.read_u32::<LittleEndian>()?;
-    println!("appid: {}", appid);
+fn apply_selection(model: &mut ParsedModel, name: &str) -> Result<(), String> {
+    let selected: Vec<Option<(LOD, LodInfo)>> = model.mlod.lods.iter().zip(model.lods.iter())
+        .map(|(lod, info)| extract_selection(lod, info, name))
+        .collect::<Result<_, _>>()?;
+    let (lods, infos) = selected.into_iter().flatten().unzip();
+    model.mlod.lods = lods;
+    model.lods = infos;
 
-    let muzzleflash = reader.read_cstring()?;
-    println!("muzzleflash: \"{}\"", muzzleflash);
+    if model.mlod.lods.is_empty() {
+        return Err(format!("Selection \"{}\" not found in any LOD.", name));
+    }
+    Ok(())
+}
 
-    let num_lods = reader.read_u32::<LittleEndian>()?;
-    println!("num lods: {}", num_lods);
+fn print_header_warnings(header: &OdolHeader) {
+    for warning in header.warnings.iter() {
+        eprintln!("Warning: {}", warning);
+    }
+}
 
-    let mut lods = Vec::new();
+fn print_lod_warnings(lod: &LOD, info: &LodInfo) {
+    for diagnostic in info.diagnostics.iter() {
+        eprintln!("Warning: LOD {}: {}", LodType::from_resolution(lod.resolution), diagnostic);
+    }
+}
 
-    for _i in 0..num_lods {
-        let lod = LOD {
-            version_major: 28,
-            version_minor: 256,
-            resolution: reader.read_f32::<LittleEndian>()?,
-            points: Vec::new(),
-            face_normals: Vec::new(),
-            faces: Vec::new(),
-            taggs: LinkedHashMap::new(),
-        };
+/// Prints what the reader collected to stderr. Diagnostics of the LODs are left out when
+/// validating or repairing, those list them on their own.
+fn print_warnings(model: &ParsedModel, options: &ReadOptions) {
+    print_header_warnings(&model.header);
+    if options.validate || options.repair {
+        return;
+    }
+    for (lod, info) in model.mlod.lods.iter().zip(model.lods.iter()) {
+        print_lod_warnings(lod, info);
+    }
+}
 
-        println!("  - {}", lod.resolution);
-        lods.push(lod);
-    }
-
-    println!("index: 0x{:x}", reader.read_u32::<LittleEndian>()?);
-
-    println!("mem lod sphere: {}", reader.read_f32::<LittleEndian>()?);
-    println!("geo lod sphere: {}", reader.read_f32::<LittleEndian>()?);
-
-    println!("point flags: {:x}, {:x}, {:x}",
-        reader.read_u32::<LittleEndian>()?,
-        reader.read_u32::<LittleEndian>()?,
-        reader.read_u32::<LittleEndian>()?);
-
-    let offset_1 = (
-        reader.read_f32::<LittleEndian>()?,
-        reader.read_f32::<LittleEndian>()?,
-        reader.read_f32::<LittleEndian>()?);
-    println!("offset 1: {:?}", offset_1);
-
-    println!("map icon color: {:x}", reader.read_u32::<LittleEndian>()?);
-    println!("map selected color: {:x}", reader.read_u32::<LittleEndian>()?);
-
-    let view_density = reader.read_f32::<LittleEndian>()?;
-    println!("view density: {}", view_density);
-
-    let bbox_min = (
-        reader.read_f32::<LittleEndian>()?,
-        reader.read_f32::<LittleEndian>()?,
-        reader.read_f32::<LittleEndian>()?);
-    let bbox_max = (
-        reader.read_f32::<LittleEndian>()?,
-        reader.read_f32::<LittleEndian>()?,
-        reader.read_f32::<LittleEndian>()?);
-    println!("bounding box: {:?} - {:?}", bbox_min, bbox_max);
-
-    println!("lod density coef: {:?}", reader.read_f32::<LittleEndian>()?);
-    println!("draw importance: {:?}", reader.read_f32::<LittleEndian>()?);
-
-    let bbox_visual_min = (
-        reader.read_f32::<LittleEndian>()?,
-        reader.read_f32::<LittleEndian>()?,
-        reader.read_f32::<LittleEndian>()?);
-    let bbox_visual_max = (
-        reader.read_f32::<LittleEndian>()?,
-        reader.read_f32::<LittleEndian>()?,
-        reader.read_f32::<LittleEndian>()?);
-    println!("bounding box visual: {:?} - {:?}", bbox_visual_min, bbox_visual_max);
-
-    let bounding_center = (
-        reader.read_f32::<LittleEndian>()?,
-        reader.read_f32::<LittleEndian>()?,
-        reader.read_f32::<LittleEndian>()?);
-    println!("bounding center: {:?}", bounding_center);
-
-    let geometry_center = (
-        reader.read_f32::<LittleEndian>()?,
-        reader.read_f32::<LittleEndian>()?,
-        reader.read_f32::<LittleEndian>()?);
-    println!("geometry center: {:?}", geometry_center);
-
-    let cog_offset = (
-        reader.read_f32::<LittleEndian>()?,
-        reader.read_f32::<LittleEndian>()?,
-        reader.read_f32::<LittleEndian>()?);
-    println!("cog offset: {:?}", cog_offset);
-
-    println!("inv inertia: {:?}", (
-        reader.read_f32::<LittleEndian>()?,
-        reader.read_f32::<LittleEndian>()?,
-        reader.read_f32::<LittleEndian>()?));
-    println!("             {:?}", (
-        reader.read_f32::<LittleEndian>()?,
-        reader.read_f32::<LittleEndian>()?,
-        reader.read_f32::<LittleEndian>()?));
-    println!("             {:?}", (
-        reader.read_f32::<LittleEndian>()?,
-        reader.read_f32::<LittleEndian>()?,
-        reader.read_f32::<LittleEndian>()?));
-
-    println!("autocenter: 0x{:x}", reader.read_u8()?);
-    println!("lock autocenter: 0x{:x}", reader.read_u8()?);
-    println!("can occlude: 0x{:x}", reader.read_u8()?);
-    println!("can be occluded: 0x{:x}", reader.read_u8()?);
-    println!("ai cover: 0x{:x}", reader.read_u8()?);
-
-    println!("skeleton ht min: {:?}", reader.read_f32::<LittleEndian>()?);
-    println!("skeleton ht max: {:?}", reader.read_f32::<LittleEndian>()?);
-    println!("skeleton af max: {:?}", reader.read_f32::<LittleEndian>()?);
-    println!("skeleton mf max: {:?}", reader.read_f32::<LittleEndian>()?);
-    println!("skeleton mf act: {:?}", reader.read_f32::<LittleEndian>()?);
-    println!("skeleton t body: {:?}", reader.read_f32::<LittleEndian>()?);
-
-    println!("force not alpha: 0x{:x}", reader.read_u8()?);
-    println!("sb source: {}", reader.read_i32::<LittleEndian>()?);
-    println!("prefer shadow volume: 0x{:x}", reader.read_u8()?);
-    println!("shadow offset: {}", reader.read_f32::<LittleEndian>()?);
-    println!("animated: 0x{:x}", reader.read_u8()?);
-
-    let skeleton_name = reader.read_cstring()?;
-    println!("skeleton name: \"{}\"", skeleton_name);
-
-    if skeleton_name != "" {
-        reader.seek(SeekFrom::Current(1))?;
-        let num_bones = reader.read_u32::<LittleEndian>()?;
-        println!("num bones: {}", num_bones);
-        for _i in 0..num_bones {
-            let name = reader.read_cstring()?;
-            let parent = reader.read_cstring()?;
-            println!("  - {} -> {}", name, parent);
-        }
-        assert_eq!(reader.read_u8()?, 0);
-    }
-
-    println!("map type: 0x{:x}", reader.read_u8()?);
-
-    let num_floats = reader.read_u32::<LittleEndian>()?;
-    println!("num floats: {}", num_floats);
-    reader.seek(SeekFrom::Current((num_floats * 4) as i64))?;
-
-    println!("mass: {:?}", reader.read_f32::<LittleEndian>()?);
-    println!("mass inv: {:?}", reader.read_f32::<LittleEndian>()?);
-    println!("armor: {:?}", reader.read_f32::<LittleEndian>()?);
-    println!("armor inv: {:?}", reader.read_f32::<LittleEndian>()?);
-
-    println!("lod indices:");
-    println!("  memory: {}", reader.read_i8()?);
-    println!("  geometry: {}", reader.read_i8()?);
-    println!("  geometry simple: {}", reader.read_i8()?);
-    println!("  geometry physx: {}", reader.read_i8()?);
-    println!("  geometry fire: {}", reader.read_i8()?);
-    println!("  geometry view: {}", reader.read_i8()?);
-    println!("  geometry view pilot: {}", reader.read_i8()?);
-    println!("  geometry view gunner: {}", reader.read_i8()?);
-    println!("  geometry view commander: {}", reader.read_i8()?);
-    println!("  geometry view cargo: {}", reader.read_i8()?);
-    println!("  land contact: {}", reader.read_i8()?);
-    println!("  roadway: {}", reader.read_i8()?);
-    println!("  paths: {}", reader.read_i8()?);
-    println!("  hitpoints: {}", reader.read_i8()?);
-
-    reader.seek(SeekFrom::Current(4))?;
-
-    println!("0x{:x}", reader.seek(SeekFrom::Current(0))?);
-
-    println!("min shadow: {}", reader.read_u32::<LittleEndian>()?);
-    println!("can blend: 0x{:x}", reader.read_u8()?);
-
-    println!("0x{:x}", reader.seek(SeekFrom::Current(0))?);
-
-    println!("class type: \"{}\"", reader.read_cstring()?);
-    println!("destruct type: \"{}\"", reader.read_cstring()?);
-
-    reader.seek(SeekFrom::Current((if version >= 73 { 1 } else { 0 } + 4) as i64))?;
-
-    println!("lod defaults:");
-    for _i in 0..num_lods {
-        println!("  - {:x} {:x} {:x}",
-            reader.read_u32::<LittleEndian>()?,
-            reader.read_u32::<LittleEndian>()?,
-            reader.read_u32::<LittleEndian>()?);
-    }
-    println!("0x{:x}", reader.seek(SeekFrom::Current(0))?);
-
-    let animations = reader.read_u8()?;
-    if animations > 0 {
-        let num_anims = reader.read_u32::<LittleEndian>()?;
-        println!("  num anims: {}", num_anims);
-        let mut animtypes: Vec<u32> = Vec::with_capacity(num_anims as usize);
-        for _i in 0..num_anims {
-            let animtype = reader.read_u32::<LittleEndian>()?;
-            animtypes.push(animtype);
-            println!("    - {}", reader.read_cstring()?);
-            println!("      type: 0x{:x}", animtype);
-            println!("      source: \"{}\"", reader.read_cstring()?);
-            println!("      value: {:?} - {:?}",
-                reader.read_f32::<LittleEndian>()?,
-                reader.read_f32::<LittleEndian>()?);
-            println!("      phase: {:?} - {:?}",
-                reader.read_f32::<LittleEndian>()?,
-                reader.read_f32::<LittleEndian>()?);
-            reader.seek(SeekFrom::Current(4))?;
-            //assert_eq!(reader.read_u32::<LittleEndian>()?, 0x38d1b717);
-            //assert_eq!(reader.read_u32::<LittleEndian>()?, 0);
-            reader.read_u32::<LittleEndian>()?;
-            println!("      source address: {}", reader.read_u32::<LittleEndian>()?);
-
-            if animtype <= 3 {
-                println!("      angle: {:?} - {:?}",
-                    reader.read_f32::<LittleEndian>()?,
-                    reader.read_f32::<LittleEndian>()?);
-            } else if animtype <= 7 {
-                println!("      offset: {:?} - {:?}",
-                    reader.read_f32::<LittleEndian>()?,
-                    reader.read_f32::<LittleEndian>()?);
-            } else if animtype == 8 {
-                reader.seek(SeekFrom::Current(4*4))?;
-            } else {
-                println!("      hide: {:?}", reader.read_f32::<LittleEndian>()?);
-                println!("      unhide: {:?}", reader.read_f32::<LittleEndian>()?);
-            }
-        }
+fn read_model(input: &Path, options: &ReadOptions, select: &Option<String>, lowercase: bool) -> ParsedModel {
+    try_read_model(input, options, select, lowercase).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    })
+}
 
-        let num_resolutions = reader.read_u32::<LittleEndian>()?;
-        println!("  num resolutions: {}", num_resolutions);
-        for _i in 0..num_resolutions {
-            let num_bones = reader.read_u32::<LittleEndian>()?;
-            for _j in 0..num_bones {
-                let num_anims = reader.read_u32::<LittleEndian>()?;
-                reader.seek(SeekFrom::Current((num_anims * 4) as i64));
-            }
-        }
-        for _i in 0..num_resolutions {
-            for animtype in animtypes.iter() {
-                let bone_name_index = reader.read_i32::<LittleEndian>()?;
-                if bone_name_index != -1 && *animtype < 8 {
-                    reader.seek(SeekFrom::Current(2 * 12));
-                }
-            }
+fn try_read_model(input: &Path, options: &ReadOptions, select: &Option<String>, lowercase: bool) -> Result<ParsedModel, String> {
+    let mut model = read_odol(input.to_path_buf(), options).map_err(|e| format!("Failed to read {}: {}", input.display(), e))?;
+    print_warnings(&model, options);
+
+    if let Some(name) = select {
+        apply_selection(&mut model, name)?;
+    }
+
+    if lowercase {
+        for (lod, info) in model.mlod.lods.iter_mut().zip(model.lods.iter_mut()) {
+            lowercase_paths(lod, info);
         }
     }
 
-    let mut lod_indices: Vec<u32> = Vec::with_capacity(num_lods as usize);
-    for _i in 0..num_lods {
-        lod_indices.push(reader.read_u32::<LittleEndian>()?);
+    Ok(model)
+}
+
+fn print_info(model: &ParsedModel) {
+    let header = &model.header;
+    println!("version:           {}", header.version);
+    println!("appid:             {}", header.appid);
+    println!("game:              {}", header.game);
+    println!("view density:      {}", header.view_density);
+    println!("lod density coef:  {}", header.lod_density_coef);
+    println!("draw importance:   {}", header.draw_importance);
+    println!("shadow offset:     {}", header.shadow_offset);
+    println!("point flags:       0x{:x}, 0x{:x}, 0x{:x}", header.point_flags.0, header.point_flags.1, header.point_flags.2);
+    println!("offset 1:          {:?}", header.offset_1);
+    println!("bounding center:   {:?}", header.bounding_center);
+    println!("geometry center:   {:?}", header.geometry_center);
+    println!("cog offset:        {:?}", header.cog_offset);
+    println!("inv inertia:       {:?}", header.inv_inertia[0]);
+    println!("                   {:?}", header.inv_inertia[1]);
+    println!("                   {:?}", header.inv_inertia[2]);
+    let flags: Vec<&str> = [
+        ("autocenter", header.autocenter),
+        ("lock-autocenter", header.lock_autocenter),
+        ("can-occlude", header.can_occlude),
+        ("can-be-occluded", header.can_be_occluded),
+        ("ai-cover", header.ai_cover),
+        ("force-not-alpha", header.force_not_alpha),
+        ("prefer-shadow-volume", header.prefer_shadow_volume),
+        ("animated", header.animated),
+    ].iter().filter(|(_, set)| *set).map(|(name, _)| *name).collect();
+    println!("flags:             {}", flags.join(" "));
+    println!("class type:        \"{}\"", header.class_type);
+    println!("destruct type:     \"{}\"", header.destruct_type);
+    println!("map type:          {}", header.map_type_name().map(|m| m.to_string()).unwrap_or(header.map_type.to_string()));
+    println!("map colors:        0x{:08x} 0x{:08x} (selected)", header.map_icon_color, header.map_selected_color);
+    println!("lods:");
+    for (i, (lod, info)) in model.mlod.lods.iter().zip(model.lods.iter()).enumerate() {
+        let (num_blocks, num_compressed, stored_size, size) = info.compression_summary();
+        println!("  {:>3}  {:<32} {:>8} points {:>8} faces  {:>4}/{:<4} arrays compressed ({} -> {} bytes)",
+            i,
+            LodType::from_resolution(lod.resolution).to_string(),
+            lod.points.len(),
+            lod.faces.len(),
+            num_compressed,
+            num_blocks,
+            stored_size,
+            size);
     }
+}
 
-    println!("lod indices: {:?}", lod_indices);
+fn expand_template(template: &str, input: &Path) -> PathBuf {
+    let dir = match input.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_string_lossy(),
+        _ => ".".into(),
+    };
+    let stem = input.file_stem().map(|s| s.to_string_lossy()).unwrap_or_default();
+    let ext = input.extension().map(|e| e.to_string_lossy()).unwrap_or_default();
+
+    PathBuf::from(template
+        .replace("{dir}", &dir)
+        .replace("{stem}", &stem)
+        .replace("{ext}", &ext))
+}
 
-    for (i, lod) in lods.iter_mut().enumerate() {
-        println!("LOD {} (0x{:x})", lod.resolution, lod_indices[i]);
-        reader.seek(SeekFrom::Start(lod_indices[i] as u64))?;
+/// What convert writes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OutFormat {
+    Mlod,
+    Json,
+    Export(Format),
+}
 
-        let num_proxies = reader.read_u32::<LittleEndian>()?;
-        println!("  num proxies: {}", num_proxies);
-        for _i in 0..num_proxies {
-            println!("    - {}", reader.read_cstring()?);
-            reader.seek(SeekFrom::Current(4*12 + 4*4))?;
+impl OutFormat {
+    fn from_name(name: &str) -> Option<OutFormat> {
+        match name.to_lowercase().as_str() {
+            "mlod" | "p3d" => Some(OutFormat::Mlod),
+            "json" => Some(OutFormat::Json),
+            name => Format::from_name(name).map(OutFormat::Export),
         }
+    }
+}
+
+/// Picks the output format from --out-format, or else the extension of the output.
+fn out_format(name: &Option<String>, output: &Option<PathBuf>) -> Result<OutFormat, String> {
+    if let Some(name) = name {
+        return OutFormat::from_name(name).ok_or_else(|| {
+            let mut names = vec!["mlod", "json"];
+            names.extend(export::FORMATS.iter().map(|(name, _)| *name));
+            format!("Unknown output format \"{}\", expected one of: {}.", name, names.join(", "))
+        });
+    }
+
+    Ok(output.as_ref()
+        .and_then(|output| output.extension())
+        .and_then(|ext| OutFormat::from_name(&ext.to_string_lossy()))
+        .unwrap_or(OutFormat::Mlod))
+}
+
+fn cmd_convert(argv: Vec<String>) {
+    let args: ConvertArgs = Docopt::new(USAGE_CONVERT)
+        .and_then(|d| d.argv(argv).deserialize())
+        .unwrap_or_else(|e| e.exit());
 
-        let num_bones_subskeleton = reader.read_u32::<LittleEndian>()?;
-        println!("  num bones subskeleton: {}", num_bones_subskeleton);
-        reader.seek(SeekFrom::Current((num_bones_subskeleton * 4) as i64))?;
+    crowbar::log::set_verbose(args.flag_verbose);
+    crowbar::log::set_profile(args.flag_profile);
+    crowbar::trace!("{:?}", args);
 
-        let num_bones_skeleton = reader.read_u32::<LittleEndian>()?;
-        println!("  num bones skeleton: {}", num_bones_skeleton);
-        for _i in 0..num_bones_skeleton {
-            let num_links = reader.read_u32::<LittleEndian>()?;
-            reader.seek(SeekFrom::Current((num_links * 4) as i64))?;
+    let inputs = if args.flag_pbo_out.is_some() { args.arg_inputs.clone() } else { vec![args.arg_input.clone()] };
+
+    if let Some(lod_type) = &args.flag_only_type {
+        if let Err(e) = check_lod_type(lod_type) {
+            convert_failed(&args, &inputs, &e);
         }
+    }
+    if args.flag_strict && (args.flag_keep_going || args.flag_repair) {
+        convert_failed(&args, &inputs, "--strict can't be combined with --keep-going or --repair.");
+    }
+    if !MLOD_VERSIONS.contains(&args.flag_mlod_version) {
+        let versions: Vec<String> = MLOD_VERSIONS.iter().map(|v| v.to_string()).collect();
+        convert_failed(&args, &inputs, &format!("Unsupported MLOD version {}, expected one of: {}.", args.flag_mlod_version, versions.join(", ")));
+    }
 
-        let num_points = reader.read_u32::<LittleEndian>()?;
-        println!("  num points: {}", num_points);
+    let options = ReadOptions {
+        keep_going: args.flag_keep_going,
+        debug_offsets: args.flag_debug_offsets,
+        skip_bytes: args.flag_skip_bytes,
+        magic: parse_magic(&args.flag_magic),
+        max_lods: args.flag_max_lods,
+        lzo: parse_lzo_backend(&args.flag_lzo_backend),
+        versions: args.flag_assert_version.clone(),
+        strict: args.flag_strict,
+        sanitize_floats: args.flag_sanitize_floats,
+        timeout: parse_timeout(args.flag_timeout),
+        skip_bounding_offset: args.flag_no_bounding_offset,
+        repair: args.flag_repair,
+        faithful: args.flag_faithful,
+        mlod_version: args.flag_mlod_version,
+        ..Default::default()
+    };
+
+    let outcomes = if let Some(pbo_path) = &args.flag_pbo_out {
+        if args.flag_output_template.is_some() || args.flag_out_format.is_some() || args.flag_low_memory
+            || args.flag_print_hash || args.flag_dump_model_cfg.is_some() {
+            convert_failed(&args, &inputs, "--pbo-out can't be combined with --output-template, --out-format, --low-memory, --print-hash or --dump-model-cfg.");
+        }
+        convert_to_pbo(&args, &options, pbo_path)
+    } else {
+        let output = match (&args.arg_output, &args.flag_output_template) {
+            (Some(_), Some(_)) => convert_failed(&args, &inputs, "Pass either <output> or --output-template, not both."),
+            (Some(output), None) => Some(output.clone()),
+            (None, Some(template)) => Some(expand_template(template, &args.arg_input)),
+            (None, None) => None,
+        };
 
-        reader.seek(SeekFrom::Current(3*4 + 3*12 + 4))?;
+        let format = out_format(&args.flag_out_format, &output).unwrap_or_else(|e| convert_failed(&args, &inputs, &e));
+        if format != OutFormat::Mlod && (args.flag_low_memory || args.flag_print_hash) {
+            convert_failed(&args, &inputs, "--low-memory and --print-hash only work for mlod output.");
+        }
+        if args.flag_low_memory && output.is_none() {
+            convert_failed(&args, &inputs, "--low-memory needs an output.");
+        }
 
-        let num_textures = reader.read_u32::<LittleEndian>()?;
-        println!("  num textures: {}", num_textures);
-        let mut textures: Vec<String> = Vec::with_capacity(num_textures as usize);
-        for _i in 0..num_textures {
-            let texture = reader.read_cstring()?;
-            println!("    - {}", texture);
-            textures.push(texture);
+        let result = convert_input(&args, &options, &output, format);
+        if let Err(e) = &result {
+            eprintln!("{}", e);
         }
+        vec![(args.arg_input.clone(), result)]
+    };
+
+    if let Some(manifest) = &args.flag_manifest {
+        write_convert_manifest(manifest, &outcomes);
+    }
+    if outcomes.iter().any(|(_, result)| result.is_err()) {
+        std::process::exit(1);
+    }
+}
+
+/// Prints why convert can't run and exits. The manifest still gets written, with the error
+/// for every input.
+fn convert_failed(args: &ConvertArgs, inputs: &[PathBuf], message: &str) -> ! {
+    eprintln!("{}", message);
+    if let Some(manifest) = &args.flag_manifest {
+        let outcomes: Vec<(PathBuf, Result<(), String>)> = inputs.iter().map(|input| (input.clone(), Err(message.to_string()))).collect();
+        write_convert_manifest(manifest, &outcomes);
+    }
+    std::process::exit(1);
+}
 
-        let num_materials = reader.read_u32::<LittleEndian>()?;
-        println!("  num materials: {}", num_materials);
-        let mut materials: Vec<String> = Vec::with_capacity(num_materials as usize);
-        for _i in 0..num_materials {
-            let path = reader.read_cstring()?;
-            println!("    - {}", path);
+/// Writes a JSON array with whether every input was converted, and the error if not.
+fn write_convert_manifest(path: &Path, outcomes: &[(PathBuf, Result<(), String>)]) {
+    let entries: Vec<ManifestEntry> = outcomes.iter().map(|(input, result)| ManifestEntry {
+        path: input.to_string_lossy().to_string(),
+        ok: result.is_ok(),
+        error: result.as_ref().err().cloned(),
+        model: None,
+    }).collect();
+
+    if let Err(e) = std::fs::write(path, serde_json::to_string(&entries).expect("Failed to serialize manifest.")) {
+        eprintln!("Failed to write {}: {}", path.display(), e);
+        std::process::exit(1);
+    }
+}
 
-            reader.seek(SeekFrom::Current(4 + 6*16 + 5*4))?;
+fn write_error(path: &Path, e: impl std::fmt::Display) -> String {
+    format!("Failed to write {}: {}", path.display(), e)
+}
 
-            let surface = reader.read_cstring()?;
-            println!("      surface: \"{}\"", surface);
+/// Converts the input of convert without --pbo-out to the output, if any.
+fn convert_input(args: &ConvertArgs, options: &ReadOptions, output: &Option<PathBuf>, format: OutFormat) -> Result<(), String> {
+    let input = &args.arg_input;
+    if args.flag_passthrough_mlod && read_magic(input).map_err(|e| format!("Failed to read {}: {}", input.display(), e))? == *b"MLOD" {
+        eprintln!("{} is already MLOD, copying.", input.display());
+        if let Some(output_path) = output {
+            std::fs::copy(input, output_path).map_err(|e| write_error(output_path, e))?;
+        }
+        return Ok(());
+    }
 
-            reader.seek(SeekFrom::Current(2*4))?;
-            let num_stages = reader.read_u32::<LittleEndian>()?;
-            println!("      num stages: {}", num_stages);
-            let num_transforms = reader.read_u32::<LittleEndian>()?;
-            println!("      num transforms: {}", num_transforms);
+    if let (true, Some(output_path)) = (args.flag_low_memory, output) {
+        return convert_low_memory(args, options, output_path);
+    }
 
-            for _j in 0..num_stages {
-                reader.seek(SeekFrom::Current(4))?;
-                println!("        - {}", reader.read_cstring()?);
-                reader.seek(SeekFrom::Current(4 + 1))?;
+    let model = read_converted(input, args, options)?;
+
+    match (format, output) {
+        (OutFormat::Mlod, Some(output_path)) => {
+            let mut timer = Timer::start(|| "MLOD".to_string());
+            let f = File::create(output_path).map_err(|e| write_error(output_path, e))?;
+            let mut f = HashWriter::new(f);
+            write_mlod(&model, &mut f).map_err(|e| write_error(output_path, e))?;
+            timer.lap("write");
+            if args.flag_print_hash {
+                println!("{}  {}", to_hex(&f.hash()), output_path.display());
             }
+        },
+        (OutFormat::Json, Some(output_path)) => {
+            let json = serde_json::to_string(&model).map_err(|e| write_error(output_path, e))?;
+            std::fs::write(output_path, format!("{}\n", json)).map_err(|e| write_error(output_path, e))?;
+        },
+        (OutFormat::Export(format), Some(output_path)) => {
+            let mut timer = Timer::start(|| format!("export {}", format.name()));
+            let lods: Vec<&LOD> = model.mlod.lods.iter().collect();
+            export::export(&lods, format, output_path, &ExportOptions::default()).map_err(|e| write_error(output_path, e))?;
+            timer.lap("write");
+        },
+        (_, None) => {},
+    }
+    if output.is_none() && args.flag_print_hash {
+        let mut f = HashWriter::new(std::io::sink());
+        write_mlod(&model, &mut f).map_err(|e| format!("Failed to write MLOD: {}", e))?;
+        println!("{}  -", to_hex(&f.hash()));
+    }
 
-            reader.seek(SeekFrom::Current((num_transforms * (4 + 3*4*4)) as i64))?;
+    if let Some(cfg_path) = &args.flag_dump_model_cfg {
+        let name = input.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        let mut f = File::create(cfg_path).map_err(|e| write_error(cfg_path, e))?;
+        write_model_cfg(&mut f, &model.header, &name).map_err(|e| write_error(cfg_path, e))?;
+    }
+    Ok(())
+}
 
-            reader.seek(SeekFrom::Current(4))?;
-            reader.read_cstring()?;
-            reader.seek(SeekFrom::Current(4 + 1))?;
+/// Reads a model and applies the options of convert that pick and strip what is written.
+fn read_converted(input: &Path, args: &ConvertArgs, options: &ReadOptions) -> Result<ParsedModel, String> {
+    let mut model = try_read_model(input, options, &args.flag_select, args.flag_lowercase_paths)?;
+    if args.flag_repair && model.lods.iter().any(|info| !info.diagnostics.is_empty()) {
+        println!("Problems found while repairing:");
+        print_diagnostics(&model);
+    }
+    if let Some(lod_type) = &args.flag_only_type {
+        retain_lods(&mut model, |_, lod| LodType::from_resolution(lod.resolution).is_type(lod_type));
+    }
+    if args.flag_skip_empty_lods {
+        retain_lods(&mut model, |_, lod| !lod.faces.is_empty());
+    }
+    // The hash is meant to be piped into sha256sum -c or compared, so it's printed alone.
+    if !args.flag_print_hash {
+        print!("{}", model);
+    }
 
-            materials.push(path);
+    for (lod, info) in model.mlod.lods.iter_mut().zip(model.lods.iter_mut()) {
+        if args.flag_strip_selections {
+            lod.taggs.clear();
+            info.selections.clear();
         }
+        if args.flag_strip_properties {
+            info.properties.clear();
+        }
+    }
+
+    Ok(model)
+}
+
+/// Name of an input in a PBO, its path relative to the working directory with backslashes.
+/// Inputs outside of the working directory only keep their file name.
+fn pbo_entry_name(input: &Path) -> String {
+    let relative = std::env::current_dir().ok()
+        .and_then(|dir| input.strip_prefix(dir).ok().map(|p| p.to_path_buf()))
+        .unwrap_or_else(|| input.to_path_buf());
+
+    let components: Option<Vec<String>> = relative.components().filter(|c| *c != Component::CurDir).map(|c| match c {
+        Component::Normal(name) => Some(name.to_string_lossy().to_string()),
+        _ => None,
+    }).collect();
+
+    match components {
+        Some(components) if !components.is_empty() => components.join("\\"),
+        _ => input.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default(),
+    }
+}
+
+/// Removes a temporary directory when dropped, also when packing fails halfway.
+struct TempDir(PathBuf);
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Writes the entries to a temporary directory and packs that with armake2, which only packs
+/// PBOs from directories.
+fn pack_pbo(entries: &[(String, Vec<u8>)], pbo_path: &Path) -> std::io::Result<()> {
+    let temp_dir = TempDir(std::env::temp_dir().join(format!("crowbar-{}", std::process::id())));
+    for (name, data) in entries {
+        let path = name.split('\\').fold(temp_dir.0.clone(), |path, part| path.join(part));
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        std::fs::write(&path, data)?;
+    }
 
-        let num_edges1 = reader.read_u32::<LittleEndian>()?;
-        println!("  num edges 1: {}", num_edges1);
-        reader.seek(SeekFrom::Current((2 * num_edges1) as i64))?;
+    // armake2 would use the name of the directory as the prefix, so leave it empty instead.
+    let mut f = File::create(pbo_path)?;
+    armake2::pbo::cmd_pack(temp_dir.0.clone(), &mut f, &vec!["prefix=".to_string()], &Vec::new())
+}
 
-        let num_edges2 = reader.read_u32::<LittleEndian>()?;
-        println!("  num edges 2: {}", num_edges2);
-        reader.seek(SeekFrom::Current((2 * num_edges2) as i64))?;
+/// Converts one input of --pbo-out into memory.
+fn convert_pbo_entry(input: &Path, name: &str, args: &ConvertArgs, options: &ReadOptions) -> Result<Vec<u8>, String> {
+    let read_error = |e: std::io::Error| format!("Failed to read {}: {}", input.display(), e);
+    if args.flag_passthrough_mlod && read_magic(input).map_err(read_error)? == *b"MLOD" {
+        eprintln!("{} is already MLOD, copying.", input.display());
+        return std::fs::read(input).map_err(read_error);
+    }
 
-        //println!("0x{:x}", reader.seek(SeekFrom::Current(0))?);
+    println!("{}:", name);
+    let model = read_converted(input, args, options)?;
+    let mut data: Vec<u8> = Vec::new();
+    write_mlod(&model, &mut data).map_err(|e| format!("Failed to write MLOD of {}: {}", input.display(), e))?;
+    Ok(data)
+}
+
+/// Converts every input into memory first, so that nothing is left behind on disk if one of
+/// them can't be read, then packs them all. Returns whether every input ended up in the PBO.
+fn convert_to_pbo(args: &ConvertArgs, options: &ReadOptions, pbo_path: &Path) -> Vec<(PathBuf, Result<(), String>)> {
+    // Paths in PBOs are case-insensitive.
+    let mut names: HashMap<String, &Path> = HashMap::new();
+    for input in args.arg_inputs.iter() {
+        if let Some(other) = names.insert(pbo_entry_name(input).to_lowercase(), input) {
+            let message = format!("{} and {} would both be packed as {}.", other.display(), input.display(), pbo_entry_name(input));
+            convert_failed(args, &args.arg_inputs, &message);
+        }
+    }
 
-        let num_faces = reader.read_u32::<LittleEndian>()?;
-        println!("  num faces: {}", num_faces);
-        reader.seek(SeekFrom::Current(6))?;
-        let mut faces: Vec<(Vec<u32>, usize, usize)> = Vec::with_capacity(num_faces as usize);
-        for _i in 0..num_faces {
-            let face_type = reader.read_u8()?;
-            let mut face: Vec<u32> = Vec::with_capacity(face_type as usize);
-            for _j in 0..face_type {
-                face.push(reader.read_u32::<LittleEndian>()?);
+    let mut entries: Vec<(String, Vec<u8>)> = Vec::with_capacity(args.arg_inputs.len());
+    let mut outcomes: Vec<(PathBuf, Result<(), String>)> = Vec::with_capacity(args.arg_inputs.len());
+    for input in args.arg_inputs.iter() {
+        let name = pbo_entry_name(input);
+        match convert_pbo_entry(input, &name, args, options) {
+            Ok(data) => {
+                entries.push((name, data));
+                outcomes.push((input.clone(), Ok(())));
+            },
+            Err(e) => {
+                eprintln!("{}", e);
+                outcomes.push((input.clone(), Err(e)));
+            },
+        }
+    }
+
+    let error = if outcomes.iter().any(|(_, result)| result.is_err()) {
+        format!("Not packed into {}, other inputs couldn't be converted.", pbo_path.display())
+    } else if let Err(e) = pack_pbo(&entries, pbo_path) {
+        let message = write_error(pbo_path, e);
+        eprintln!("{}", message);
+        message
+    } else {
+        return outcomes;
+    };
+
+    for (_, result) in outcomes.iter_mut() {
+        if result.is_ok() {
+            *result = Err(error.clone());
+        }
+    }
+    outcomes
+}
+
+/// Like `convert_input`, but one LOD at a time.
+fn convert_low_memory(args: &ConvertArgs, options: &ReadOptions, output_path: &Path) -> Result<(), String> {
+    let mut input = open_input(&args.arg_input).map_err(|e| format!("Failed to read {}: {}", args.arg_input.display(), e))?;
+    let mut f = File::create(output_path).map_err(|e| write_error(output_path, e))?;
+
+    let mut found_selection = false;
+    let mut selection_error: Option<String> = None;
+    let result = convert_streaming(&mut input, &mut f, options, |lod, info| {
+        // Once a selection can't be extracted, the output is only finished, not filled.
+        if selection_error.is_some() {
+            return false;
+        }
+        if let Some(lod_type) = &args.flag_only_type {
+            if !LodType::from_resolution(lod.resolution).is_type(lod_type) {
+                return false;
+            }
+        }
+        if let Some(name) = &args.flag_select {
+            match extract_selection(lod, info, name) {
+                Ok(Some((selected_lod, selected_info))) => {
+                    *lod = selected_lod;
+                    *info = selected_info;
+                    found_selection = true;
+                },
+                Ok(None) => return false,
+                Err(e) => {
+                    selection_error = Some(e);
+                    return false;
+                },
             }
-            //println!("    {}: {:?}", face_type, face);
-            faces.push((face, 0xffffff, 0xffffff));
         }
+        if args.flag_lowercase_paths {
+            lowercase_paths(lod, info);
+        }
+        if args.flag_skip_empty_lods && lod.faces.is_empty() {
+            return false;
+        }
+        if args.flag_repair && !info.diagnostics.is_empty() {
+            println!("Problems found while repairing LOD {}:", LodType::from_resolution(lod.resolution));
+            for diagnostic in info.diagnostics.iter() {
+                println!("  {}", diagnostic);
+            }
+        } else if !args.flag_repair {
+            print_lod_warnings(lod, info);
+        }
+        if args.flag_strip_selections {
+            lod.taggs.clear();
+            info.selections.clear();
+        }
+        if args.flag_strip_properties {
+            info.properties.clear();
+        }
+        true
+    });
 
-        //println!("0x{:x}", reader.seek(SeekFrom::Current(0))?);
+    let header = result.map_err(|e| format!("Failed to convert {}: {}", args.arg_input.display(), e))?;
+    print_header_warnings(&header);
+    if let Some(e) = selection_error {
+        return Err(e);
+    }
+    if let Some(name) = &args.flag_select {
+        if !found_selection {
+            return Err(format!("Selection \"{}\" not found in any LOD.", name));
+        }
+    }
 
-        let num_sections = reader.read_u32::<LittleEndian>()?;
-        println!("  num sections: {}", num_sections);
-        let mut sections: Vec<(u32, u32)> = Vec::with_capacity(num_sections as usize);
-        for _i in 0..num_sections {
-            let face_from = reader.read_u32::<LittleEndian>()?;
-            let face_to = reader.read_u32::<LittleEndian>()?;
-            println!("    - {} - {}", face_from, face_to);
-            sections.push((face_from, face_to));
+    // The LOD count is patched in after all LODs were written, so hash the finished file.
+    if args.flag_print_hash {
+        let mut f = HashWriter::new(std::io::sink());
+        let mut written = File::open(output_path).map_err(|e| format!("Failed to read {}: {}", output_path.display(), e))?;
+        std::io::copy(&mut written, &mut f).map_err(|e| format!("Failed to hash {}: {}", output_path.display(), e))?;
+        println!("{}  {}", to_hex(&f.hash()), output_path.display());
+    }
 
-            reader.seek(SeekFrom::Current(3*4))?;
+    if let Some(cfg_path) = &args.flag_dump_model_cfg {
+        let name = args.arg_input.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        let mut f = File::create(cfg_path).map_err(|e| write_error(cfg_path, e))?;
+        write_model_cfg(&mut f, &header, &name).map_err(|e| write_error(cfg_path, e))?;
+    }
+    Ok(())
+}
 
-            let texture_index = reader.read_u16::<LittleEndian>()?;
-            println!("      texture index: {}", texture_index);
+fn print_paths(inputs: &[PathBuf], textures: bool, materials: bool, options: &ReadOptions) {
+    let mut paths: BTreeSet<String> = BTreeSet::new();
+    for input in inputs {
+        let (input_textures, input_materials) = open_input(input).and_then(|mut reader| read_paths(&mut reader, options)).unwrap_or_else(|e| {
+            eprintln!("Failed to read {}: {}", input.display(), e);
+            std::process::exit(1);
+        });
 
-            reader.seek(SeekFrom::Current(4))?;
+        if textures {
+            paths.extend(input_textures);
+        }
+        if materials {
+            paths.extend(input_materials);
+        }
+    }
 
-            let material_index = reader.read_i32::<LittleEndian>()?;
-            println!("      material index: {}", material_index);
-            if material_index == -1 {
-                reader.seek(SeekFrom::Current(1))?;
-            }
+    for path in paths {
+        println!("{}", path);
+    }
+}
 
-            let num_stages = reader.read_u32::<LittleEndian>()?;
-            println!("      num stages: {}", num_stages);
-            reader.seek(SeekFrom::Current((4*num_stages) as i64))?;
+/// An input in the manifests of info and convert.
+#[derive(Serialize)]
+struct ManifestEntry {
+    path: String,
+    ok: bool,
+    error: Option<String>,
+    /// What info lists of the model, left out by convert and for inputs that can't be read.
+    #[serde(flatten)]
+    model: Option<ManifestModel>,
+}
+
+#[derive(Serialize)]
+struct ManifestModel {
+    version: u32,
+    appid: u32,
+    lods: Vec<ManifestLod>,
+    textures: BTreeSet<String>,
+    materials: BTreeSet<String>,
+}
+
+#[derive(Serialize)]
+struct ManifestLod {
+    resolution: f32,
+    #[serde(rename = "type")]
+    lod_type: String,
+    num_points: usize,
+    num_faces: usize,
+}
 
-            let coll_info = reader.read_u32::<LittleEndian>()?;
-            println!("      coll info: {}", coll_info);
-            if coll_info > 0 {
-                reader.seek(SeekFrom::Current(2*12 + 4 + 12 + 4))?;
+fn manifest_entry(input: &Path, options: &ReadOptions) -> ManifestEntry {
+    let path = input.to_string_lossy().to_string();
+
+    let model = match read_odol(input.to_path_buf(), options) {
+        Ok(model) => model,
+        Err(e) => return ManifestEntry { path, ok: false, error: Some(e.to_string()), model: None },
+    };
+    print_warnings(&model, options);
+
+    let mut textures: BTreeSet<String> = BTreeSet::new();
+    let mut materials: BTreeSet<String> = BTreeSet::new();
+    let lods: Vec<ManifestLod> = model.mlod.lods.iter().map(|lod| {
+        for face in lod.faces.iter() {
+            if !face.texture.is_empty() && !face.texture.starts_with('#') {
+                textures.insert(face.texture.clone());
             }
+            if !face.material.is_empty() {
+                materials.insert(face.material.clone());
+            }
+        }
+
+        ManifestLod {
+            resolution: lod.resolution,
+            lod_type: LodType::from_resolution(lod.resolution).to_string(),
+            num_points: lod.points.len(),
+            num_faces: lod.faces.len(),
+        }
+    }).collect();
+
+    ManifestEntry {
+        path,
+        ok: true,
+        error: None,
+        model: Some(ManifestModel {
+            version: model.header.version,
+            appid: model.header.appid,
+            lods,
+            textures,
+            materials,
+        }),
+    }
+}
 
-            let mut face_index = 0;
-            for mut face in faces.iter_mut() {
-                if face_index > face_to {
-                    break;
-                }
+/// Writes a JSON array with an entry for every input, returns whether all could be read.
+fn write_manifest(inputs: &[PathBuf], options: &ReadOptions, path: &Path, pretty: bool) -> bool {
+    let entries: Vec<ManifestEntry> = inputs.iter().map(|input| manifest_entry(input, options)).collect();
+    let success = entries.iter().all(|entry| entry.ok);
 
-                if face_index >= face_from {
-                    face.1 = texture_index as usize;
-                    face.2 = material_index as usize;
-                }
+    let json = if pretty { serde_json::to_string_pretty(&entries) } else { serde_json::to_string(&entries) };
+    std::fs::write(path, json.expect("Failed to serialize manifest.")).expect("Failed to write manifest.");
+    success
+}
 
-                face_index += face.0.len() as u32 * 4 + 1;
-            }
+/// Prints a value as JSON, on a single line or indented.
+fn print_json<T: Serialize>(value: &T, pretty: bool) {
+    let json = if pretty { serde_json::to_string_pretty(value) } else { serde_json::to_string(value) };
+    println!("{}", json.expect("Failed to serialize JSON."));
+}
+
+fn parse_magic(magic: &str) -> [u8; 4] {
+    let bytes = magic.as_bytes();
+    if bytes.len() != 4 {
+        eprintln!("The magic has to be four bytes long, got \"{}\".", magic);
+        std::process::exit(1);
+    }
+    [bytes[0], bytes[1], bytes[2], bytes[3]]
+}
+
+fn parse_lzo_backend(name: &str) -> Lzo {
+    Lzo::from_name(name).unwrap_or_else(|| {
+        let names: Vec<&str> = LZO_BACKENDS.iter().map(|(name, _)| *name).collect();
+        eprintln!("Unknown LZO backend \"{}\", expected one of: {}.", name, names.join(", "));
+        std::process::exit(1);
+    })
+}
+
+fn parse_timeout(seconds: Option<f64>) -> Option<Duration> {
+    seconds.map(|seconds| {
+        if !seconds.is_finite() || seconds <= 0.0 {
+            eprintln!("The timeout has to be a positive number of seconds, got {}.", seconds);
+            std::process::exit(1);
         }
+        Duration::from_secs_f64(seconds)
+    })
+}
 
-        // TODO: handle selections properly
-        let num_selections = reader.read_u32::<LittleEndian>()?;
-        println!("  num selections: {}", num_selections);
-        let mut selections: Vec<(String, Vec<u32>, Vec<u32>, Vec<u32>, Vec<u8>)> = Vec::with_capacity(num_selections as usize);
-        for _i in 0..num_selections {
-            let name = reader.read_cstring()?;
-            println!("    - {}", name);
-
-            let num_f = reader.read_u32::<LittleEndian>()?;
-            println!("      num faces: {}", num_f);
-            let mut faces: Vec<u32> = Vec::with_capacity(num_f as usize);
-            if num_f > 0 {
-                let mut cursor = Cursor::new(read_compressed_array(&mut reader, (num_f * 4) as usize)?);
-                for _j in 0..num_f {
-                    faces.push(cursor.read_u32::<LittleEndian>()?);
-                }
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// Prints the LOD and face count of every input as CSV, returns whether all could be read.
+fn print_counts(inputs: &[PathBuf], options: &ReadOptions) -> bool {
+    let mut success = true;
+    println!("path,lods,faces");
+    for input in inputs {
+        match open_input(input).and_then(|mut reader| count_faces(&mut reader, options)) {
+            Ok(counts) => {
+                let num_faces: u64 = counts.iter().map(|c| *c as u64).sum();
+                println!("{},{},{}", csv_field(&input.to_string_lossy()), counts.len(), num_faces);
+            },
+            Err(e) => {
+                eprintln!("Failed to read {}: {}", input.display(), e);
+                success = false;
             }
+        }
+    }
 
-            let c = reader.read_u32::<LittleEndian>()?;
-            reader.seek(SeekFrom::Current((c*4) as i64))?;
+    success
+}
 
-            reader.seek(SeekFrom::Current(1))?;
+fn print_diagnostics(model: &ParsedModel) {
+    for (i, (lod, info)) in model.mlod.lods.iter().zip(model.lods.iter()).enumerate() {
+        for diagnostic in info.diagnostics.iter() {
+            println!("  LOD {} ({}): {}", i, LodType::from_resolution(lod.resolution), diagnostic);
+        }
+    }
+}
 
-            let num_s = reader.read_u32::<LittleEndian>()?;
-            println!("      num sections: {}", num_s);
-            let mut sections: Vec<u32> = Vec::with_capacity(num_s as usize);
-            if num_s > 0 {
-                let mut cursor = Cursor::new(read_compressed_array(&mut reader, (num_s * 4) as usize)?);
-                for _j in 0..num_s {
-                    sections.push(cursor.read_u32::<LittleEndian>()?);
-                }
-            }
+/// Prints the index, resolution and type of every LOD from the header alone.
+fn list_lods(input: &Path, options: &ReadOptions) {
+    let header = open_input(input)
+        .and_then(|reader| Ok(Offset::new(reader, options.skip_bytes)?))
+        .and_then(|mut reader| parse_header_with_options(&mut reader, options))
+        .unwrap_or_else(|e| {
+            eprintln!("Failed to read {}: {}", input.display(), e);
+            std::process::exit(1);
+        });
+    print_header_warnings(&header);
+
+    for (i, resolution) in header.resolutions.iter().enumerate() {
+        println!("{}\t{}\t{}", i, resolution, resolution.lod_type().name());
+    }
+}
 
-            let num_v = reader.read_u32::<LittleEndian>()?;
-            println!("      num vertices: {}", num_v);
-            let mut verts: Vec<u32> = Vec::with_capacity(num_v as usize);
-            if num_v > 0 {
-                let mut cursor = Cursor::new(read_compressed_array(&mut reader, (num_v * 4) as usize)?);
-                for _j in 0..num_v {
-                    verts.push(cursor.read_u32::<LittleEndian>()?);
-                }
+/// Prints every diagnostic of the inputs, returns whether there were none.
+fn validate(inputs: &[PathBuf], options: &ReadOptions) -> bool {
+    let mut valid = true;
+    for input in inputs {
+        let model = match read_odol(input.to_path_buf(), options) {
+            Ok(model) => model,
+            Err(e) => {
+                println!("{}: {}", input.display(), e);
+                valid = false;
+                continue;
             }
+        };
+        print_warnings(&model, options);
 
-            let num_w = reader.read_u32::<LittleEndian>()?;
-            let vertweights: Vec<u8> = if num_w > 0 {
-                read_compressed_array(&mut reader, num_w as usize)?
-            } else {
-                Vec::new()
-            };
-
-            selections.push((name, faces, sections, verts, vertweights));
+        let num_diagnostics: usize = model.lods.iter().map(|info| info.diagnostics.len()).sum();
+        if num_diagnostics == 0 {
+            println!("{}: OK", input.display());
+            continue;
         }
 
-        let num_properties = reader.read_u32::<LittleEndian>()?;
-        println!("  num properties: {}", num_properties);
-        for _i in 0..num_properties {
-            println!("    - {} = \"{}\"", reader.read_cstring()?, reader.read_cstring()?);
+        valid = false;
+        println!("{}: {} problems", input.display(), num_diagnostics);
+        print_diagnostics(&model);
+    }
+
+    valid
+}
+
+fn cmd_info(argv: Vec<String>) {
+    let args: InfoArgs = Docopt::new(USAGE_INFO)
+        .and_then(|d| d.argv(argv).deserialize())
+        .unwrap_or_else(|e| e.exit());
+
+    crowbar::log::set_verbose(args.flag_verbose);
+
+    if args.flag_strict && args.flag_keep_going {
+        eprintln!("--strict can't be combined with --keep-going.");
+        std::process::exit(1);
+    }
+
+    let options = ReadOptions {
+        keep_going: args.flag_keep_going,
+        debug_offsets: args.flag_debug_offsets,
+        skip_bytes: args.flag_skip_bytes,
+        magic: parse_magic(&args.flag_magic),
+        max_lods: args.flag_max_lods,
+        lzo: parse_lzo_backend(&args.flag_lzo_backend),
+        versions: args.flag_assert_version.clone(),
+        strict: args.flag_strict,
+        sanitize_floats: args.flag_sanitize_floats,
+        timeout: parse_timeout(args.flag_timeout),
+        ..Default::default()
+    };
+
+    if args.flag_texture_list || args.flag_material_list {
+        print_paths(&args.arg_input, args.flag_texture_list, args.flag_material_list, &options);
+        return;
+    }
+
+    if let Some(manifest) = &args.flag_manifest {
+        if !write_manifest(&args.arg_input, &options, manifest, args.flag_json_pretty) {
+            std::process::exit(1);
         }
+        return;
+    }
 
-        let num_frames = reader.read_u32::<LittleEndian>()?;
-        assert_eq!(num_frames, 0);
+    if args.flag_count_only {
+        if !print_counts(&args.arg_input, &options) {
+            std::process::exit(1);
+        }
+        return;
+    }
 
-        reader.seek(SeekFrom::Current(3*4 + 1 + 4))?;
+    if args.flag_validate_only {
+        let options = ReadOptions { validate: true, ..options };
+        if !validate(&args.arg_input, &options) {
+            std::process::exit(1);
+        }
+        return;
+    }
 
-        //println!("0x{:x}", reader.seek(SeekFrom::Current(0))?);
+    if args.flag_compare {
+        if args.arg_input.len() != 2 {
+            eprintln!("--compare needs exactly two inputs.");
+            std::process::exit(1);
+        }
 
-        let num_pointflags = reader.read_u32::<LittleEndian>()?;
-        println!("  num pointflags: {}", num_pointflags);
-        let comp_type = reader.read_u8()?;
-        if comp_type == 1 {
-            reader.seek(SeekFrom::Current(4))?;
-        } else if comp_type == 0 {
-            reader.seek(SeekFrom::Current((num_pointflags * 4) as i64))?;
+        let a = read_model(&args.arg_input[0], &options, &None, false);
+        let b = read_model(&args.arg_input[1], &options, &None, false);
+        let comparison = Comparison::new(&a, &b);
+        if args.flag_json_pretty {
+            print_json(&comparison, true);
+        } else if args.flag_json {
+            print_json(&comparison, false);
         } else {
-            unreachable!();
-        }
-
-        //println!("0x{:x}", reader.seek(SeekFrom::Current(0))?);
-
-        let uv_scale: (f32, f32, f32, f32) = (
-            reader.read_f32::<LittleEndian>()?,
-            reader.read_f32::<LittleEndian>()?,
-            reader.read_f32::<LittleEndian>()?,
-            reader.read_f32::<LittleEndian>()?);
-        println!("  uv scale: ({}, {}, {}, {})", uv_scale.0, uv_scale.1, uv_scale.2, uv_scale.3);
-
-        let uv_range: (f32, f32) = (uv_scale.2 - uv_scale.0, uv_scale.3 - uv_scale.1);
-
-        // TODO: handle UVs properly
-        let num_uvs = reader.read_u32::<LittleEndian>()?;
-        println!("  num uvs: {}", num_uvs);
-        let mut uvs: Vec<(f32, f32)> = Vec::with_capacity(num_uvs as usize);
-        if num_uvs > 0 {
-            let fill = reader.read_u8()?;
-            if fill == 1 {
-                let u: f32 = ((reader.read_i16::<LittleEndian>()? as i32 + 0x7fff) as f32) / ((2 * 0x7fff) as f32);
-                let v: f32 = ((reader.read_i16::<LittleEndian>()? as i32 + 0x7fff) as f32) / ((2 * 0x7fff) as f32);
-                let uv: (f32, f32) = (u * uv_range.0 + uv_scale.0, v * uv_range.1 + uv_scale.1);
-                uvs.resize(num_uvs as usize, uv);
-            } else if fill == 0 {
-                let decompressed = read_compressed_array(&mut reader, (num_uvs * 4) as usize)?;
-                let mut cursor = Cursor::new(decompressed);
-
-                for i in 0..num_uvs {
-                    let u: f32 = ((cursor.read_i16::<LittleEndian>()? as i32 + 0x7fff) as f32) / ((2 * 0x7fff) as f32);
-                    let v: f32 = ((cursor.read_i16::<LittleEndian>()? as i32 + 0x7fff) as f32) / ((2 * 0x7fff) as f32);
-                    let uv: (f32, f32) = (u * uv_range.0 + uv_scale.0, v * uv_range.1 + uv_scale.1);
-                    if i < 20 {
-                        println!("    - ({}, {})", uv.0, uv.1);
-                    }
-                    uvs.push(uv);
-                }
-            } else {
-                unreachable!();
-            }
+            print!("{}", comparison);
         }
 
-        let num_uvsets = reader.read_u32::<LittleEndian>()?;
-        if num_uvs > 0 {
-            for _i in 1..num_uvsets {
-                let _uv_scale: (f32, f32, f32, f32) = (
-                    reader.read_f32::<LittleEndian>()?,
-                    reader.read_f32::<LittleEndian>()?,
-                    reader.read_f32::<LittleEndian>()?,
-                    reader.read_f32::<LittleEndian>()?);
-
-                let num_uvs = reader.read_u32::<LittleEndian>()?;
-
-                let fill = reader.read_u8()?;
-                if fill == 1 {
-                    reader.seek(SeekFrom::Current(4))?;
-                } else if fill == 0 {
-                    read_compressed_array(&mut reader, (num_uvs * 4) as usize)?;
-                } else {
-                    unreachable!();
-                }
-            }
+        if !comparison.is_empty() {
+            std::process::exit(1);
         }
+        return;
+    }
 
-        assert_eq!(reader.read_u32::<LittleEndian>()?, num_points);
-        println!("  num points: {}", num_points);
-        let mut points: Vec<(f32, f32, f32)> = Vec::with_capacity(num_points as usize);
-        if num_points > 0 {
-            let decompressed = read_compressed_array(&mut reader, (num_points * 12) as usize)?;
-            let mut cursor = Cursor::new(decompressed);
+    if args.arg_input.len() > 1 {
+        eprintln!("Multiple inputs are only supported with --texture-list, --material-list, --validate-only, --count-only, --compare and --manifest.");
+        std::process::exit(1);
+    }
 
-            for i in 0..num_points {
-                let point = (cursor.read_f32::<LittleEndian>()?, cursor.read_f32::<LittleEndian>()?, cursor.read_f32::<LittleEndian>()?);
-                if i < 20 {
-                    println!("    - {:?}", point);
-                }
-                points.push(point);
-            }
+    if args.flag_list_lods {
+        list_lods(&args.arg_input[0], &options);
+        return;
+    }
+
+    let model = read_model(&args.arg_input[0], &options, &None, false);
+
+    if args.flag_dump_geometry {
+        for lod in model.mlod.lods.iter() {
+            print!("{}", GeometryDump(lod));
+        }
+    } else if args.flag_compression {
+        let report = model.compression_report();
+        if args.flag_json_pretty {
+            print_json(&report, true);
+        } else if args.flag_json {
+            print_json(&report, false);
+        } else {
+            print!("{}", report);
+        }
+    } else if args.flag_selection_report {
+        let report = model.selection_report();
+        if args.flag_json_pretty {
+            print_json(&report, true);
+        } else if args.flag_json {
+            print_json(&report, false);
+        } else {
+            print!("{}", report);
+        }
+    } else if args.flag_stats {
+        let stats = Stats::from_p3d(&model.mlod);
+        if args.flag_json_pretty {
+            print_json(&stats, true);
+        } else if args.flag_json {
+            print_json(&stats, false);
+        } else {
+            print!("{}", stats);
         }
+    } else if args.flag_json_pretty {
+        print_json(&model, true);
+    } else if args.flag_json {
+        print_json(&model, false);
+    } else {
+        print_info(&model);
+    }
+}
 
-        for p in points {
-            lod.points.push(Point {
-                coords: (
-                    p.0 + bounding_center.0,
-                    p.1 + bounding_center.1,
-                    p.2 + bounding_center.2
-                ),
-                flags: 0
-            });
-            lod.face_normals.push((0.0, 0.0, 0.0)); // TODO
-        }
-
-        for (verts, t, m) in faces.iter() {
-            let vertices: Vec<Vertex> = verts.iter().rev().map(|i| Vertex {
-                point_index: *i,
-                normal_index: *i,
-                uv: uvs[*i as usize],
-            }).collect();
-
-            lod.faces.push(Face {
-                vertices,
-                flags: 0,
-                texture: textures.get(*t).map(|t| t.clone()).unwrap_or(String::new()),
-                material: materials.get(*m).map(|t| t.clone()).unwrap_or(String::new())
-            })
-        }
-
-        for (name, selfaces, selsections, selverts, mut selvertweights) in selections {
-            if selvertweights.len() == 0 {
-                selvertweights = Vec::with_capacity(selverts.len());
-                selvertweights.resize(selverts.len(), 0x1);
-            }
+fn cmd_export(argv: Vec<String>) {
+    let args: ExportArgs = Docopt::new(USAGE_EXPORT)
+        .and_then(|d| d.argv(argv).deserialize())
+        .unwrap_or_else(|e| e.exit());
 
-            assert_eq!(selverts.len(), selvertweights.len());
+    crowbar::log::set_verbose(args.flag_verbose);
+    crowbar::log::set_profile(args.flag_profile);
 
-            let mut mlod_verts: Vec<u8> = Vec::with_capacity(num_points as usize);
-            let mut mlod_faces: Vec<u8> = Vec::with_capacity(num_faces as usize);
-            mlod_verts.resize(num_points as usize, 0);
-            mlod_faces.resize(num_faces as usize, 0);
+    let mut format = Format::from_name(&args.flag_format).unwrap_or_else(|| {
+        let names: Vec<&str> = export::FORMATS.iter().map(|(name, _)| *name).collect();
+        eprintln!("Unknown format \"{}\", expected one of: {}.", args.flag_format, names.join(", "));
+        std::process::exit(1);
+    });
+    if format == Format::Gltf && matches!(args.arg_output.extension(), Some(e) if e.eq_ignore_ascii_case("glb")) {
+        format = Format::Glb;
+    }
 
-            for i in selfaces {
-                mlod_faces[i as usize] = 0x1;
-            }
+    if args.flag_first_lod_only && (args.flag_lod.is_some() || args.flag_lod_index.is_some() || args.flag_only_type.is_some() || args.flag_merge_lods) {
+        eprintln!("--first-lod-only can't be combined with --lod, --lod-index, --only-type or --merge-lods.");
+        std::process::exit(1);
+    }
+    if args.flag_facet_normals && args.flag_smooth_normals {
+        eprintln!("--facet-normals can't be combined with --smooth-normals.");
+        std::process::exit(1);
+    }
+    if args.flag_split && args.flag_merge_lods {
+        eprintln!("--split can't be combined with --merge-lods.");
+        std::process::exit(1);
+    }
+    if args.flag_strict && args.flag_keep_going {
+        eprintln!("--strict can't be combined with --keep-going.");
+        std::process::exit(1);
+    }
+    if let Some(lod_type) = &args.flag_only_type {
+        if let Err(e) = check_lod_type(lod_type) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
 
-            for s in selsections {
-                let section = sections[s as usize];
-                for i in section.0..section.1 {
-                    if i >= num_faces {
-                        break;
-                    }
-
-                    mlod_faces[i as usize] = 0x1;
-                    for j in faces[i as usize].0.iter() {
-                        mlod_verts[*j as usize] = 0x1;
-                    }
-                }
+    let options = ReadOptions {
+        keep_going: args.flag_keep_going,
+        debug_offsets: args.flag_debug_offsets,
+        skip_bytes: args.flag_skip_bytes,
+        magic: parse_magic(&args.flag_magic),
+        max_lods: args.flag_max_lods,
+        lzo: parse_lzo_backend(&args.flag_lzo_backend),
+        versions: args.flag_assert_version.clone(),
+        strict: args.flag_strict,
+        sanitize_floats: args.flag_sanitize_floats,
+        timeout: parse_timeout(args.flag_timeout),
+        skip_bounding_offset: args.flag_no_bounding_offset,
+        first_lod_only: args.flag_first_lod_only,
+        ..Default::default()
+    };
+
+    // LODs are picked before extracting the selection, so that indices refer to the file.
+    let mut model = read_model(&args.arg_input, &options, &None, args.flag_lowercase_paths);
+
+    let indices: Vec<usize> = args.flag_lod_index.as_deref().unwrap_or("").split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| match s.parse::<usize>() {
+            Ok(i) if i < model.mlod.lods.len() => i,
+            _ => {
+                eprintln!("Invalid LOD index \"{}\", the model has {} LODs.", s, model.mlod.lods.len());
+                std::process::exit(1);
             }
+        })
+        .collect();
+    let specs: Vec<&str> = args.flag_lod.as_deref().unwrap_or("").split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    let only_type = args.flag_only_type.as_deref();
+    let picked = !indices.is_empty() || !specs.is_empty() || only_type.is_some();
+    if picked {
+        retain_lods(&mut model, |i, lod| {
+            indices.contains(&i)
+                || specs.iter().any(|s| lod_matches(lod, s))
+                || only_type.is_some_and(|t| LodType::from_resolution(lod.resolution).is_type(t))
+        });
+    } else if args.flag_merge_lods {
+        retain_lods(&mut model, |_, lod| matches!(LodType::from_resolution(lod.resolution), LodType::Visual(_)));
+    }
 
-            for (i,w) in selverts.iter().zip(selvertweights.iter()) {
-                mlod_verts[*i as usize] = *w;
-            }
+    if let Some(name) = &args.flag_select {
+        if let Err(e) = apply_selection(&mut model, name) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+
+    if !args.flag_include_empty_lods {
+        retain_lods(&mut model, |_, lod| !lod.faces.is_empty());
+    }
 
-            mlod_verts.append(&mut mlod_faces);
-            lod.taggs.insert(name, mlod_verts.into_boxed_slice());
+    if args.flag_sort_faces_by_material {
+        for lod in model.mlod.lods.iter_mut() {
+            export::sort_faces_by_material(lod);
         }
     }
 
-    Ok(P3D {
-        version: 257,
-        lods: lods
-    })
+    let lods: Vec<&LOD> = if !picked && !args.flag_merge_lods && !args.flag_split {
+        model.mlod.lods.iter().take(1).collect()
+    } else {
+        model.mlod.lods.iter().collect()
+    };
+
+    if lods.is_empty() {
+        eprintln!("No LODs to export.");
+        std::process::exit(1);
+    }
+
+    let export_options = ExportOptions {
+        flip_winding: args.flag_flip_winding,
+        flip_uv_v: args.flag_flip_uv_v,
+        normals: if args.flag_smooth_normals {
+            Some(Normals::Smooth)
+        } else if args.flag_facet_normals {
+            Some(Normals::Facet)
+        } else {
+            None
+        },
+    };
+
+    let mut timer = Timer::start(|| format!("export {}", format.name()));
+    if args.flag_split {
+        for (lod, path) in lods.iter().zip(split_paths(&args.arg_output, &lods)) {
+            export::export(&[lod], format, &path, &export_options).expect("Failed to export");
+        }
+    } else {
+        export::export(&lods, format, &args.arg_output, &export_options).expect("Failed to export");
+    }
+    timer.lap("write");
+}
+
+/// Output paths for exporting every LOD to its own file. LODs with the same name get their
+/// index appended.
+fn split_paths(output: &Path, lods: &[&LOD]) -> Vec<PathBuf> {
+    let stem = output.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let ext = output.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+
+    let names: Vec<String> = lods.iter().map(|lod| export::lod_name(lod)).collect();
+    names.iter().enumerate().map(|(i, name)| {
+        let name = if names.iter().filter(|n| *n == name).count() > 1 {
+            format!("{}_{}", name, i)
+        } else {
+            name.clone()
+        };
+        output.with_file_name(format!("{}_{}{}", stem, name, ext))
+    }).collect()
 }
 
 fn main() {
+    let mut argv: Vec<String> = std::env::args().collect();
+
+    // crowbar <input> [<output>] predates the subcommands and means convert
+    if let Some(first) = argv.get(1) {
+        if !COMMANDS.contains(&first.as_str()) && first != "-h" && first != "--help" && first != "--version" {
+            argv.insert(1, "convert".to_string());
+        }
+    }
+
     let args: Args = Docopt::new(USAGE)
-        .and_then(|d| d.deserialize())
+        .and_then(|d| d.argv(argv.clone()).options_first(true).deserialize())
         .unwrap_or_else(|e| e.exit());
 
     if args.flag_version {
@@ -733,12 +1586,14 @@ fn main() {
         std::process::exit(0);
     }
 
-    println!("{:?}", args);
-
-    let mlod = read_odol(args.arg_input).expect("Failed to read ODOL");
-
-    if let Some(output_path) = args.arg_output {
-        let mut f = File::create(output_path).expect("Failed to open output.");
-        mlod.write(&mut f).expect("Failed to write MLOD");
+    match args.arg_command.as_deref() {
+        Some("convert") => cmd_convert(argv),
+        Some("info") => cmd_info(argv),
+        Some("export") => cmd_export(argv),
+        Some(command) => {
+            eprintln!("Unknown command \"{}\".", command);
+            std::process::exit(1);
+        },
+        None => unreachable!(),
     }
 }
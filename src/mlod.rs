@@ -0,0 +1,353 @@
+use std::io::{BufWriter, Cursor, Error, ErrorKind, Read, Seek, SeekFrom, Write};
+
+use armake2::p3d::{Face, LOD, Vertex};
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use crate::io::WriteExt;
+use crate::error::CrowbarError;
+use crate::odol::{check_mlod_version, read_odol_from_reader, read_odol_lods, LodInfo, OdolHeader, ParsedModel, ReadOptions};
+
+// armake2 can write P3Ds, but its LODs keep taggs in a map keyed by name, which can't hold the
+// several #Property# taggs a LOD with more than one named property needs.
+
+fn write_vertex<O: Write>(vertex: &Vertex, output: &mut O) -> Result<(), Error> {
+    output.write_u32::<LittleEndian>(vertex.point_index)?;
+    output.write_u32::<LittleEndian>(vertex.normal_index)?;
+    output.write_f32::<LittleEndian>(vertex.uv.0)?;
+    output.write_f32::<LittleEndian>(vertex.uv.1)?;
+    Ok(())
+}
+
+fn write_face<O: Write>(face: &Face, output: &mut O) -> Result<(), Error> {
+    output.write_u32::<LittleEndian>(face.vertices.len() as u32)?;
+
+    for vertex in face.vertices.iter() {
+        write_vertex(vertex, output)?;
+    }
+    if face.vertices.len() == 3 {
+        output.write_all(&[0; 16])?;
+    }
+
+    output.write_u32::<LittleEndian>(face.flags)?;
+    output.write_cstring(&face.texture)?;
+    output.write_cstring(&face.material)?;
+    Ok(())
+}
+
+fn write_tagg<O: Write>(name: &str, data: &[u8], output: &mut O) -> Result<(), Error> {
+    output.write_all(&[1])?;
+    output.write_cstring(name)?;
+    output.write_u32::<LittleEndian>(data.len() as u32)?;
+    output.write_all(data)?;
+    Ok(())
+}
+
+/// Builds the data of a #Property# tagg, a 64 byte name followed by a 64 byte value.
+fn property_tagg(name: &str, value: &str) -> Result<[u8; 128], Error> {
+    if name.len() >= 64 || value.len() >= 64 {
+        return Err(Error::new(ErrorKind::InvalidData, format!("Named property \"{}\" is too long for MLOD.", name)));
+    }
+
+    let mut data = [0; 128];
+    data[..name.len()].copy_from_slice(name.as_bytes());
+    data[64..64 + value.len()].copy_from_slice(value.as_bytes());
+    Ok(data)
+}
+
+/// Builds the data of a #UVSet# tagg, the set index followed by a UV for every vertex of every
+/// face. The UV sets in `LodInfo` are indexed by point.
+fn uv_set_tagg(lod: &LOD, index: u32, uvs: &[(f32, f32)]) -> Result<Vec<u8>, Error> {
+    let mut data: Vec<u8> = Vec::new();
+    data.write_u32::<LittleEndian>(index)?;
+
+    for face in lod.faces.iter() {
+        for vertex in face.vertices.iter() {
+            let uv = uvs.get(vertex.point_index as usize).copied().unwrap_or((0.0, 0.0));
+            data.write_f32::<LittleEndian>(uv.0)?;
+            data.write_f32::<LittleEndian>(uv.1)?;
+        }
+    }
+
+    Ok(data)
+}
+
+pub fn write_lod<O: Write>(lod: &LOD, info: &LodInfo, output: &mut O) -> Result<(), Error> {
+    output.write_all(b"P3DM")?;
+    output.write_u32::<LittleEndian>(lod.version_major)?;
+    output.write_u32::<LittleEndian>(lod.version_minor)?;
+    output.write_u32::<LittleEndian>(lod.points.len() as u32)?;
+    output.write_u32::<LittleEndian>(lod.face_normals.len() as u32)?;
+    output.write_u32::<LittleEndian>(lod.faces.len() as u32)?;
+    output.write_u32::<LittleEndian>(0)?;
+
+    for point in lod.points.iter() {
+        output.write_f32::<LittleEndian>(point.coords.0)?;
+        output.write_f32::<LittleEndian>(point.coords.1)?;
+        output.write_f32::<LittleEndian>(point.coords.2)?;
+        output.write_u32::<LittleEndian>(point.flags)?;
+    }
+
+    for normal in lod.face_normals.iter() {
+        output.write_f32::<LittleEndian>(normal.0)?;
+        output.write_f32::<LittleEndian>(normal.1)?;
+        output.write_f32::<LittleEndian>(normal.2)?;
+    }
+
+    for face in lod.faces.iter() {
+        write_face(face, output)?;
+    }
+
+    output.write_all(b"TAGG")?;
+
+    for (name, data) in lod.taggs.iter() {
+        write_tagg(name, data, output)?;
+    }
+
+    // The primary UV set is already stored in the vertices.
+    for (i, uvs) in info.uv_sets.iter().enumerate() {
+        write_tagg("#UVSet#", &uv_set_tagg(lod, i as u32 + 1, uvs)?, output)?;
+    }
+
+    for (name, value) in info.properties.iter() {
+        write_tagg("#Property#", &property_tagg(name, value)?, output)?;
+    }
+
+    write_tagg("#EndOfFile#", &[], output)?;
+
+    output.write_f32::<LittleEndian>(lod.resolution)?;
+
+    Ok(())
+}
+
+pub fn write_mlod<O: Write>(model: &ParsedModel, output: &mut O) -> Result<(), Error> {
+    let mut writer = BufWriter::new(output);
+
+    writer.write_all(b"MLOD")?;
+    writer.write_u32::<LittleEndian>(model.mlod.version)?;
+    writer.write_u32::<LittleEndian>(model.mlod.lods.len() as u32)?;
+
+    for (lod, info) in model.mlod.lods.iter().zip(model.lods.iter()) {
+        write_lod(lod, info, &mut writer)?;
+    }
+
+    Ok(())
+}
+
+/// Converts an ODOL to MLOD one LOD at a time, so that only one LOD is in memory at once.
+/// `edit` is called on every LOD before it is written and can drop it by returning false. The
+/// number of LODs is written before it is known and fixed afterwards, so the output has to be
+/// seekable. Returns the header of the ODOL.
+pub fn convert_streaming<I, O, F>(input: &mut I, output: &mut O, options: &ReadOptions, mut edit: F) -> Result<OdolHeader, CrowbarError>
+where
+    I: Read + Seek,
+    O: Write + Seek,
+    F: FnMut(&mut LOD, &mut LodInfo) -> bool,
+{
+    check_mlod_version(options)?;
+
+    let mut writer = BufWriter::new(output);
+    let start = writer.stream_position()?;
+
+    writer.write_all(b"MLOD")?;
+    writer.write_u32::<LittleEndian>(options.mlod_version)?;
+    writer.write_u32::<LittleEndian>(0)?;
+
+    let mut num_lods: u32 = 0;
+    let header = read_odol_lods(input, options, |mut lod, mut info| {
+        if edit(&mut lod, &mut info) {
+            write_lod(&lod, &info, &mut writer)?;
+            num_lods += 1;
+        }
+        Ok(())
+    })?;
+
+    let end = writer.stream_position()?;
+    writer.seek(SeekFrom::Start(start + 8))?;
+    writer.write_u32::<LittleEndian>(num_lods)?;
+    writer.seek(SeekFrom::Start(end))?;
+    writer.flush()?;
+
+    Ok(header)
+}
+
+/// Converts an ODOL in memory to MLOD with the default options, without printing anything.
+pub fn convert_bytes(input: &[u8]) -> Result<Vec<u8>, CrowbarError> {
+    let model = read_odol_from_reader(&mut Cursor::new(input), &ReadOptions::default())?;
+
+    let mut output: Vec<u8> = Vec::new();
+    write_mlod(&model, &mut output)?;
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use byteorder::ReadBytesExt;
+
+    use crate::fixture;
+    use crate::io::ReadExt;
+    use crate::odol::MLOD_VERSION;
+
+    /// What the tests look at of a LOD of a written MLOD. armake2's reader keeps taggs in a
+    /// map, which loses all but one of several taggs with the same name.
+    struct MlodLod {
+        point_flags: Vec<u32>,
+        /// Point index and UV of every vertex of every face.
+        faces: Vec<Vec<(u32, (f32, f32))>>,
+        taggs: Vec<(String, Vec<u8>)>,
+    }
+
+    fn read_mlod(data: &[u8]) -> Vec<MlodLod> {
+        let mut reader = Cursor::new(data);
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic).unwrap();
+        assert_eq!(&magic, b"MLOD");
+        assert_eq!(reader.read_u32::<LittleEndian>().unwrap(), MLOD_VERSION);
+
+        let num_lods = reader.read_u32::<LittleEndian>().unwrap();
+        (0..num_lods).map(|_| {
+            reader.read_exact(&mut magic).unwrap();
+            assert_eq!(&magic, b"P3DM");
+            reader.seek(SeekFrom::Current(2 * 4)).unwrap();
+            let num_points = reader.read_u32::<LittleEndian>().unwrap();
+            let num_normals = reader.read_u32::<LittleEndian>().unwrap();
+            let num_faces = reader.read_u32::<LittleEndian>().unwrap();
+            reader.seek(SeekFrom::Current(4)).unwrap();
+
+            let point_flags = (0..num_points).map(|_| {
+                reader.seek(SeekFrom::Current(3 * 4)).unwrap();
+                reader.read_u32::<LittleEndian>().unwrap()
+            }).collect();
+            reader.seek(SeekFrom::Current(num_normals as i64 * 12)).unwrap();
+
+            let faces = (0..num_faces).map(|_| {
+                let num_vertices = reader.read_u32::<LittleEndian>().unwrap();
+                let mut vertices = Vec::new();
+                for i in 0..4 {
+                    let point = reader.read_u32::<LittleEndian>().unwrap();
+                    reader.read_u32::<LittleEndian>().unwrap();
+                    let uv = (reader.read_f32::<LittleEndian>().unwrap(), reader.read_f32::<LittleEndian>().unwrap());
+                    if i < num_vertices {
+                        vertices.push((point, uv));
+                    }
+                }
+                reader.read_u32::<LittleEndian>().unwrap();
+                reader.read_cstring().unwrap();
+                reader.read_cstring().unwrap();
+                vertices
+            }).collect();
+
+            reader.read_exact(&mut magic).unwrap();
+            assert_eq!(&magic, b"TAGG");
+            let mut taggs = Vec::new();
+            loop {
+                assert_eq!(reader.read_u8().unwrap(), 1);
+                let name = reader.read_cstring().unwrap();
+                let mut tagg = vec![0; reader.read_u32::<LittleEndian>().unwrap() as usize];
+                reader.read_exact(&mut tagg).unwrap();
+                if name == "#EndOfFile#" {
+                    break;
+                }
+                taggs.push((name, tagg));
+            }
+            reader.read_f32::<LittleEndian>().unwrap();
+
+            MlodLod { point_flags, faces, taggs }
+        }).collect()
+    }
+
+    fn convert(model: &fixture::Model) -> Vec<MlodLod> {
+        read_mlod(&convert_bytes(&model.to_bytes()).unwrap())
+    }
+
+    /// Names and values of the #Property# taggs of a LOD.
+    fn properties(lod: &MlodLod) -> Vec<(String, String)> {
+        let field = |data: &[u8]| String::from_utf8(data.iter().take_while(|b| **b != 0).copied().collect()).unwrap();
+        lod.taggs.iter()
+            .filter(|(name, _)| name == "#Property#")
+            .map(|(_, data)| (field(&data[..64]), field(&data[64..])))
+            .collect()
+    }
+
+    fn assert_uv_eq(actual: (f32, f32), expected: (f32, f32)) {
+        // UVs are stored as 16 bit fractions of their range.
+        assert!((actual.0 - expected.0).abs() < 1e-4 && (actual.1 - expected.1).abs() < 1e-4, "{:?} != {:?}", actual, expected);
+    }
+
+    /// A LOD with a quad and a triangle.
+    fn quad_and_triangle() -> fixture::Lod {
+        let mut lod = fixture::Lod::new(1.0);
+        lod.points = vec![(0.0, 0.0, 0.0), (1.0, 0.0, 0.0), (1.0, 0.0, 1.0), (0.0, 0.0, 1.0), (2.0, 0.0, 0.0)];
+        lod.faces = vec![vec![0, 1, 2, 3], vec![1, 4, 2]];
+        lod.sections.push(fixture::Section { faces: 0..2, texture: 0, material: -1 });
+        lod
+    }
+
+    #[test]
+    fn secondary_uv_sets_become_uvset_taggs() {
+        let mut lod = quad_and_triangle();
+        let uv_sets: Vec<Vec<(f32, f32)>> = (0..3)
+            .map(|set| (0..5).map(|i| (i as f32 * 0.25, set as f32 * 0.5)).collect())
+            .collect();
+        lod.uv_sets = uv_sets.clone();
+
+        let lods = convert(&fixture::Model::new(vec![lod]));
+        let lod = &lods[0];
+
+        for (point, uv) in lod.faces.iter().flatten() {
+            assert_uv_eq(*uv, uv_sets[0][*point as usize]);
+        }
+
+        let uv_set_taggs: Vec<&Vec<u8>> = lod.taggs.iter()
+            .filter(|(name, _)| name == "#UVSet#")
+            .map(|(_, data)| data)
+            .collect();
+        assert_eq!(uv_set_taggs.len(), 2);
+        for (i, data) in uv_set_taggs.into_iter().enumerate() {
+            let mut reader = Cursor::new(data);
+            assert_eq!(reader.read_u32::<LittleEndian>().unwrap(), i as u32 + 1);
+            for (point, _) in lod.faces.iter().flatten() {
+                let uv = (reader.read_f32::<LittleEndian>().unwrap(), reader.read_f32::<LittleEndian>().unwrap());
+                assert_uv_eq(uv, uv_sets[i + 1][*point as usize]);
+            }
+            assert_eq!(reader.position(), data.len() as u64);
+        }
+    }
+
+    #[test]
+    fn can_blend_becomes_a_geometry_property() {
+        let geometry = |class: &str| {
+            let mut lod = quad_and_triangle();
+            lod.resolution = 1e13;
+            lod.properties.push(("class".to_string(), class.to_string()));
+            lod
+        };
+
+        let mut model = fixture::Model::new(vec![quad_and_triangle(), geometry("house")]);
+        model.can_blend = true;
+        let lods = convert(&model);
+        assert!(properties(&lods[0]).is_empty());
+        let geometry_properties = properties(&lods[1]);
+        assert!(geometry_properties.contains(&("class".to_string(), "house".to_string())));
+        assert!(geometry_properties.contains(&("canblend".to_string(), "1".to_string())));
+
+        let lods = convert(&fixture::Model::new(vec![geometry("house")]));
+        assert!(properties(&lods[0]).contains(&("canblend".to_string(), "0".to_string())));
+    }
+
+    #[test]
+    fn clip_flags_become_point_flags() {
+        // Keep height above land, decal, sky fog and a user value of 3.
+        let mut lod = quad_and_triangle();
+        lod.point_flags = vec![0, 0, 0x800 | 0x1000 | 0x8000 | 0x30_0000, 0, 0];
+        let lods = convert(&fixture::Model::new(vec![lod]));
+        assert_eq!(lods[0].point_flags, [0, 0, 0x8 | 0x100 | 0x2000 | 0x3_0000, 0, 0]);
+
+        // On surface, stored once for all points.
+        let mut lod = quad_and_triangle();
+        lod.point_flags = vec![0x100; 5];
+        let lods = convert(&fixture::Model::new(vec![lod]));
+        assert_eq!(lods[0].point_flags, [0x1; 5]);
+    }
+}
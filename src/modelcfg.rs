@@ -0,0 +1,177 @@
+use std::io::{Error, Write};
+
+use crate::odol::{OdolAnimation, OdolHeader};
+
+/// Names of the animation types as used in model.cfg, by their index in the ODOL.
+pub const ANIMATION_TYPES: [&str; 10] = [
+    "rotation",
+    "rotationX",
+    "rotationY",
+    "rotationZ",
+    "translation",
+    "translationX",
+    "translationY",
+    "translationZ",
+    "direct",
+    "hide",
+];
+
+const SOURCE_ADDRESSES: [&str; 3] = ["clamp", "mirror", "loop"];
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "\"\""))
+}
+
+/// The ODOL stores the range parameters in a form binarize computed from the config, hiding
+/// and direct animations don't map back to a plain pair of values.
+fn range_entries(animation: &OdolAnimation) -> Vec<(&'static str, f32)> {
+    let params = &animation.params;
+    match animation.anim_type {
+        0..=3 => vec![("angle0", params[0]), ("angle1", params[1])],
+        4..=7 => vec![("offset0", params[0]), ("offset1", params[1])],
+        9 => vec![("hideValue", params[0]), ("unHideValue", params[1])],
+        _ => Vec::new(),
+    }
+}
+
+fn write_animation<O: Write>(output: &mut O, animation: &OdolAnimation) -> Result<(), Error> {
+    writeln!(output, "            class {} {{", animation.name)?;
+    match ANIMATION_TYPES.get(animation.anim_type as usize) {
+        Some(anim_type) => writeln!(output, "                type = {};", quote(anim_type))?,
+        None => writeln!(output, "                // Unknown animation type {}.", animation.anim_type)?,
+    }
+    writeln!(output, "                source = {};", quote(&animation.source))?;
+    if let Some(source_address) = SOURCE_ADDRESSES.get(animation.source_address as usize) {
+        writeln!(output, "                sourceAddress = {};", quote(source_address))?;
+    }
+    writeln!(output, "                selection = {};", quote(animation.bone.as_deref().unwrap_or("")))?;
+    // The axis is only stored as positions, the name of the memory selection is lost.
+    writeln!(output, "                axis = \"\";")?;
+    writeln!(output, "                minValue = {:?};", animation.value.0)?;
+    writeln!(output, "                maxValue = {:?};", animation.value.1)?;
+    writeln!(output, "                minPhase = {:?};", animation.phase.0)?;
+    writeln!(output, "                maxPhase = {:?};", animation.phase.1)?;
+    for (key, value) in range_entries(animation) {
+        writeln!(output, "                {} = {:?};", key, value)?;
+    }
+    writeln!(output, "            }};")?;
+    Ok(())
+}
+
+/// Writes a model.cfg with the skeleton and animations of the model, for the model class
+/// `name`. Axis selections can't be recovered and are left empty.
+pub fn write_model_cfg<O: Write>(output: &mut O, header: &OdolHeader, name: &str) -> Result<(), Error> {
+    if !header.skeleton_name.is_empty() {
+        writeln!(output, "class CfgSkeletons {{")?;
+        writeln!(output, "    class {} {{", header.skeleton_name)?;
+        writeln!(output, "        skeletonInherit = \"\";")?;
+        writeln!(output, "        skeletonBones[] = {{")?;
+        let bones: Vec<String> = header.bones.iter()
+            .map(|(bone, parent)| format!("            {}, {}", quote(bone), quote(parent)))
+            .collect();
+        if !bones.is_empty() {
+            writeln!(output, "{}", bones.join(",\n"))?;
+        }
+        writeln!(output, "        }};")?;
+        writeln!(output, "    }};")?;
+        writeln!(output, "}};")?;
+        writeln!(output)?;
+    }
+
+    writeln!(output, "class CfgModels {{")?;
+    writeln!(output, "    class {} {{", name)?;
+    writeln!(output, "        skeletonName = {};", quote(&header.skeleton_name))?;
+    writeln!(output, "        htMin = {:?};", header.skeleton_ht_min)?;
+    writeln!(output, "        htMax = {:?};", header.skeleton_ht_max)?;
+    writeln!(output, "        afMax = {:?};", header.skeleton_af_max)?;
+    writeln!(output, "        mfMax = {:?};", header.skeleton_mf_max)?;
+    writeln!(output, "        mfAct = {:?};", header.skeleton_mf_act)?;
+    writeln!(output, "        tBody = {:?};", header.skeleton_t_body)?;
+    writeln!(output, "        class Animations {{")?;
+    for animation in header.animations.iter() {
+        write_animation(output, animation)?;
+    }
+    writeln!(output, "        }};")?;
+    writeln!(output, "    }};")?;
+    writeln!(output, "}};")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    use crate::fixture;
+    use crate::odol::parse_header;
+
+    #[test]
+    fn rotation_and_translation() {
+        let mut model = fixture::Model::new(vec![fixture::Lod::new(1.0)]);
+        model.skeleton_name = "door_skeleton".to_string();
+        model.bones = vec![("door".to_string(), "".to_string()), ("handle".to_string(), "door".to_string())];
+        let mut rotation = fixture::Animation::new(0, "door_rot", "door", Some(0));
+        rotation.params = vec![0.0, 1.5];
+        let mut translation = fixture::Animation::new(4, "handle_move", "handle", Some(1));
+        translation.value = (-1.0, 1.0);
+        translation.phase = (0.25, 0.75);
+        translation.params = vec![-0.125, 0.25];
+        model.animations = vec![rotation, translation];
+
+        let header = parse_header(&mut Cursor::new(model.to_bytes())).unwrap();
+        let mut output: Vec<u8> = Vec::new();
+        write_model_cfg(&mut output, &header, "door").unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), r#"class CfgSkeletons {
+    class door_skeleton {
+        skeletonInherit = "";
+        skeletonBones[] = {
+            "door", "",
+            "handle", "door"
+        };
+    };
+};
+
+class CfgModels {
+    class door {
+        skeletonName = "door_skeleton";
+        htMin = 0.0;
+        htMax = 0.0;
+        afMax = 0.0;
+        mfMax = 0.0;
+        mfAct = 0.0;
+        tBody = 0.0;
+        class Animations {
+            class door_rot {
+                type = "rotation";
+                source = "door";
+                sourceAddress = "clamp";
+                selection = "door";
+                axis = "";
+                minValue = 0.0;
+                maxValue = 1.0;
+                minPhase = 0.0;
+                maxPhase = 1.0;
+                angle0 = 0.0;
+                angle1 = 1.5;
+            };
+            class handle_move {
+                type = "translation";
+                source = "handle";
+                sourceAddress = "clamp";
+                selection = "handle";
+                axis = "";
+                minValue = -1.0;
+                maxValue = 1.0;
+                minPhase = 0.25;
+                maxPhase = 0.75;
+                offset0 = -0.125;
+                offset1 = 0.25;
+            };
+        };
+    };
+};
+"#);
+    }
+}
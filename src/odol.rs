@@ -0,0 +1,2609 @@
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::fmt;
+#[cfg(not(feature = "wasm"))]
+use std::fs::File;
+#[cfg(not(feature = "wasm"))]
+use std::io::{BufRead, BufReader};
+use std::io::{Cursor, Error, ErrorKind, Read, Seek, SeekFrom};
+#[cfg(not(feature = "wasm"))]
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+#[cfg(not(feature = "wasm"))]
+use flate2::read::GzDecoder;
+use linked_hash_map::LinkedHashMap;
+use armake2::p3d::{P3D, LOD, Face, Vertex, Point};
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use serde_json::{Map, Value};
+
+use crate::error::CrowbarError;
+use crate::io::{Offset, ReadExt, ReadSeek};
+use crate::lod::{LodType, Resolution};
+use crate::log::{verbose, Timer};
+use crate::lzo::{self, Lzo, LzoBackend, LzoError};
+
+/// Order of the indices of special LODs in the header, -1 if the model has none.
+pub const SPECIAL_LOD_NAMES: [&str; 14] = [
+    "memory",
+    "geometry",
+    "geometry simple",
+    "geometry physx",
+    "geometry fire",
+    "geometry view",
+    "geometry view pilot",
+    "geometry view gunner",
+    "geometry view commander",
+    "geometry view cargo",
+    "land contact",
+    "roadway",
+    "paths",
+    "hitpoints",
+];
+
+/// Values of the map named property, in the order of the map type in the header. Objects
+/// without the property are "hide".
+pub const MAP_TYPES: [&str; 41] = [
+    "tree", "small tree", "bush", "building", "house", "forest border", "forest triangle",
+    "forest square", "church", "chapel", "cross", "rock", "bunker", "fortress", "fountain",
+    "view-tower", "lighthouse", "quay", "fuelstation", "hospital", "fence", "wall", "hide",
+    "busstop", "road", "forest", "transmitter", "stack", "ruin", "tourism", "watertower",
+    "track", "main road", "rocks", "power lines", "railway", "power solar", "power wave",
+    "power wind", "shipwreck", "trail",
+];
+
+#[derive(Debug, Clone)]
+pub struct OdolAnimation {
+    pub name: String,
+    pub anim_type: u32,
+    pub source: String,
+    /// Min and max value of the source.
+    pub value: (f32, f32),
+    /// Min and max phase.
+    pub phase: (f32, f32),
+    pub source_address: u32,
+    /// Angles for rotations, offsets for translations, hide and unhide values for hides and
+    /// the axis position and direction, angle and axis offset for direct animations.
+    pub params: Vec<f32>,
+    /// Skeleton bone moved by the animation, from the first resolution that has one.
+    pub bone: Option<String>,
+}
+
+/// Size of the axis stored per resolution after the bone of an animation of the given type, or
+/// None for unknown types. Rotations and translations have an axis position and direction.
+/// Direct animations store theirs with the animation instead, hiding has none.
+fn axis_size(anim_type: u32) -> Option<i64> {
+    match anim_type {
+        0..=7 => Some(2 * 12),
+        8 | 9 => Some(0),
+        _ => None,
+    }
+}
+
+/// Steam app ID of Arma 3, which binarize writes into models made with its tools.
+const APPID_ARMA3: u32 = 107410;
+/// Steam app ID of DayZ.
+const APPID_DAYZ: u32 = 221100;
+
+/// Game a model was binarized for, going by its appid.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Game {
+    Arma3,
+    DayZ,
+    Unknown(u32),
+}
+
+impl Game {
+    pub fn from_appid(appid: u32) -> Game {
+        match appid {
+            APPID_ARMA3 => Game::Arma3,
+            APPID_DAYZ => Game::DayZ,
+            _ => Game::Unknown(appid),
+        }
+    }
+}
+
+impl fmt::Display for Game {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Game::Arma3 => write!(f, "Arma 3"),
+            Game::DayZ => write!(f, "DayZ"),
+            Game::Unknown(appid) => write!(f, "unknown (appid {})", appid),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct OdolHeader {
+    pub version: u32,
+    pub appid: u32,
+    /// Game the model was made for, going by its appid.
+    pub game: Game,
+    pub muzzleflash: String,
+    pub resolutions: Vec<Resolution>,
+    pub lod_offsets: Vec<u32>,
+    pub index: u32,
+    pub lod_sphere_memory: f32,
+    pub lod_sphere_geometry: f32,
+    /// Point flags that apply to the whole model. Usually 0, 0 and 0xc000000, which are
+    /// believed to be the remarks and hints the flags of all points are combined into.
+    pub point_flags: (u32, u32, u32),
+    /// An offset of the whole model with unknown use, mostly the same as the bounding center.
+    /// Not applied to the points.
+    pub offset_1: (f32, f32, f32),
+    /// Packed ARGB color of the object on the map.
+    pub map_icon_color: u32,
+    /// Packed ARGB color of the object on the map while selected.
+    pub map_selected_color: u32,
+    pub view_density: f32,
+    pub bbox_min: (f32, f32, f32),
+    pub bbox_max: (f32, f32, f32),
+    pub bbox_visual_min: (f32, f32, f32),
+    pub bbox_visual_max: (f32, f32, f32),
+    /// Binarize moves all points by minus this, so that the model is centered. Points are
+    /// moved back by it when reading, unless `ReadOptions::skip_bounding_offset` is set.
+    pub bounding_center: (f32, f32, f32),
+    /// Center of mass of the geometry LOD, in the centered ODOL coordinates. Binarize derives
+    /// it from the point masses, so it is lost if the masses change after converting.
+    pub geometry_center: (f32, f32, f32),
+    /// Offset of the center of gravity from the center of mass, also from the geometry LOD.
+    pub cog_offset: (f32, f32, f32),
+    pub lod_density_coef: f32,
+    pub draw_importance: f32,
+    pub shadow_offset: f32,
+    /// Rows of the inverse inertia tensor of the geometry LOD.
+    pub inv_inertia: [(f32, f32, f32); 3],
+    pub autocenter: bool,
+    pub lock_autocenter: bool,
+    pub can_occlude: bool,
+    pub can_be_occluded: bool,
+    pub ai_cover: bool,
+    pub force_not_alpha: bool,
+    pub prefer_shadow_volume: bool,
+    pub animated: bool,
+    /// Thermal imaging parameters of the model class, htMin, htMax, afMax, mfMax, mfAct and
+    /// tBody in model.cfg. Named after the skeleton because they are stored next to it.
+    pub skeleton_ht_min: f32,
+    pub skeleton_ht_max: f32,
+    pub skeleton_af_max: f32,
+    pub skeleton_mf_max: f32,
+    pub skeleton_mf_act: f32,
+    pub skeleton_t_body: f32,
+    pub sb_source: i32,
+    pub skeleton_name: String,
+    /// Bone names and their parents.
+    pub bones: Vec<(String, String)>,
+    /// Index into `MAP_TYPES`.
+    pub map_type: u8,
+    pub mass: f32,
+    pub mass_inv: f32,
+    pub armor: f32,
+    pub armor_inv: f32,
+    /// LOD indices in the order of `SPECIAL_LOD_NAMES`.
+    pub special_lods: [i8; 14],
+    pub min_shadow: u32,
+    pub can_blend: bool,
+    pub class_type: String,
+    pub destruct_type: String,
+    pub lod_defaults: Vec<(u32, u32, u32)>,
+    pub animations: Vec<OdolAnimation>,
+    /// Problems with the model as a whole that don't stop it from being read, like several
+    /// LODs with the same resolution.
+    pub warnings: Vec<String>,
+}
+
+/// Default version of the returned P3Ds, the one Object Builder writes.
+pub const MLOD_VERSION: u32 = 257;
+
+/// MLOD versions the result can be tagged with, the ones armake2 can write.
+pub const MLOD_VERSIONS: &[u32] = &[MLOD_VERSION];
+
+#[derive(Debug)]
+pub struct ReadOptions {
+    pub keep_going: bool,
+    /// Keep points in ODOL-local coordinates instead of moving them by the bounding center.
+    pub skip_bounding_offset: bool,
+    /// Print the offset and the surrounding bytes to stderr when a parse fails.
+    pub debug_offsets: bool,
+    /// Collect violated format assumptions in `LodInfo::diagnostics` instead of failing, and
+    /// continue with the next LOD when one can't be read at all.
+    pub validate: bool,
+    /// Only read the visual LOD with the most detail and skip all others.
+    pub first_lod_only: bool,
+    /// Like `validate`, but meant for getting as much as possible out of a corrupt file. Faces
+    /// and selection members that reference missing data are dropped, LODs that can't be read
+    /// completely are kept as far as they were read. Everything dropped is listed in
+    /// `LodInfo::diagnostics`.
+    pub repair: bool,
+    /// Number of bytes in front of the model, for files with an extra header. Offsets in the
+    /// model are taken as relative to its start.
+    pub skip_bytes: u64,
+    /// Expected magic at the start of the model, for probing variants of the format.
+    pub magic: [u8; 4],
+    /// Upper limit for the number of LODs, above which the count is taken as corrupt.
+    pub max_lods: u32,
+    /// Keep the vertices of faces in the order of the ODOL instead of reversing them to the
+    /// MLOD winding. Re-binarizing the result gives back the original vertex order, but the
+    /// faces point inwards in Object Builder and exporters.
+    pub faithful: bool,
+    /// Implementation used to decompress LZO arrays.
+    pub lzo: Lzo,
+    /// ODOL versions that are accepted. Any other version fails right after it was read,
+    /// even if it is supported. Empty to accept all supported versions.
+    pub versions: Vec<u32>,
+    /// Fail on everything that is otherwise only warned about or collected in
+    /// `LodInfo::diagnostics`, for checking that a file is exactly as binarize writes it.
+    /// Overrides `keep_going`, `validate` and `repair`.
+    pub strict: bool,
+    /// Replace NaN and infinite values in points, UVs and the bounding data of the header
+    /// with 0. They are counted either way, and fail when `strict`.
+    pub sanitize_floats: bool,
+    /// Longest time reading a model may take, checked before every LOD and while searching
+    /// for the size of LZO blocks, so that one pathological file can't stall a batch.
+    pub timeout: Option<Duration>,
+    /// Version of the returned P3D, one of `MLOD_VERSIONS`.
+    pub mlod_version: u32,
+}
+
+impl Default for ReadOptions {
+    fn default() -> ReadOptions {
+        ReadOptions {
+            keep_going: false,
+            skip_bounding_offset: false,
+            debug_offsets: false,
+            validate: false,
+            first_lod_only: false,
+            repair: false,
+            skip_bytes: 0,
+            magic: *b"ODOL",
+            max_lods: 64,
+            faithful: false,
+            lzo: Lzo::default(),
+            versions: Vec::new(),
+            strict: false,
+            sanitize_floats: false,
+            timeout: None,
+            mlod_version: MLOD_VERSION,
+        }
+    }
+}
+
+/// How an array was stored in the file.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct CompressedBlock {
+    /// File offset of the array, at the byte with the compression type.
+    pub offset: u64,
+    /// Whether the array was LZO-compressed or stored raw.
+    pub compressed: bool,
+    /// Size of the array in the file in bytes, excluding the compression type.
+    pub stored_size: usize,
+    /// Size of the array after decompression in bytes.
+    pub size: usize,
+}
+
+/// Per-LOD data that armake2's LOD has no place for, in the same order as the LODs of the MLOD.
+#[derive(Debug, Default, Clone)]
+pub struct LodInfo {
+    /// Named properties, written as #Property# taggs.
+    pub properties: Vec<(String, String)>,
+    /// Secondary UV sets, the first set is stored in the vertices of the MLOD.
+    pub uv_sets: Vec<Vec<(f32, f32)>>,
+    /// Every compressed array read for this LOD, in file order.
+    pub blocks: Vec<CompressedBlock>,
+    /// Named selections, the same data as the selection taggs of the LOD.
+    pub selections: Vec<Selection>,
+    /// Assumptions about the format this LOD doesn't hold to. Harmless ones are always
+    /// collected, the others only when reading with `ReadOptions::validate` or `repair`.
+    pub diagnostics: Vec<String>,
+    /// The materials the faces reference, with what the ODOL keeps of their rvmats.
+    pub materials: Vec<Material>,
+}
+
+/// A material embedded in a LOD. Binarize copies the stages of the rvmat into the ODOL, the
+/// other rvmat parameters aren't read.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Material {
+    pub path: String,
+    pub surface: String,
+    /// Stages in order, followed by the stage for the terrain texture index map.
+    pub stages: Vec<MaterialStage>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct MaterialStage {
+    pub texture: String,
+    /// Texture filter, as stored by binarize.
+    pub filter: u32,
+    /// UV source and transformation matrix of the stage, if it references a valid one.
+    pub transform: Option<StageTransform>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct StageTransform {
+    /// UV source, as stored by binarize.
+    pub uv_source: u32,
+    /// Rotation/scale columns followed by the translation, like `ReadExt::read_matrix4x3`.
+    pub matrix: [(f32, f32, f32); 4],
+}
+
+/// Flattened, with the UV source and the matrix next to the texture.
+impl Serialize for MaterialStage {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut json = serializer.serialize_struct("MaterialStage", 4)?;
+        json.serialize_field("texture", &self.texture)?;
+        json.serialize_field("filter", &self.filter)?;
+        json.serialize_field("uv_source", &self.transform.map(|transform| transform.uv_source))?;
+        json.serialize_field("transform", &self.transform.map(|transform| transform.matrix))?;
+        json.end()
+    }
+}
+
+/// Converts a selection weight from 0 to 1 to the byte MLOD selection taggs store per point.
+/// 0 is unselected, 1 a full weight and everything else counts down from 255 for the lowest.
+pub fn mlod_weight_byte(weight: f32) -> u8 {
+    if weight <= 0.0 || weight.is_nan() {
+        0
+    } else if weight >= 1.0 {
+        1
+    } else {
+        (256.0 - weight * 255.0).round().clamp(2.0, 255.0) as u8
+    }
+}
+
+/// Inverse of `mlod_weight_byte`.
+pub fn mlod_weight(byte: u8) -> f32 {
+    match byte {
+        0 => 0.0,
+        1 => 1.0,
+        b => (256 - b as u32) as f32 / 255.0,
+    }
+}
+
+/// A named selection, resolved to indices into the points and faces of its LOD.
+#[derive(Debug, Default, Clone)]
+pub struct Selection {
+    pub name: String,
+    /// Selected points in ascending order.
+    pub points: Vec<u32>,
+    /// Weight of every selected point from 0 to 1, in the same order.
+    pub weights: Vec<f32>,
+    /// Selected faces in ascending order.
+    pub faces: Vec<u32>,
+}
+
+impl LodInfo {
+    /// Returns the number of arrays, how many of those were compressed and their total stored
+    /// and decompressed sizes.
+    pub fn compression_summary(&self) -> (usize, usize, usize, usize) {
+        (
+            self.blocks.len(),
+            self.blocks.iter().filter(|b| b.compressed).count(),
+            self.blocks.iter().map(|b| b.stored_size).sum(),
+            self.blocks.iter().map(|b| b.size).sum(),
+        )
+    }
+
+    /// Returns the names of the selections every face of a LOD with `num_faces` faces belongs
+    /// to, in the order of the selections, e.g. to color faces by selection when exporting.
+    pub fn face_selections(&self, num_faces: usize) -> Vec<Vec<&str>> {
+        let mut face_selections: Vec<Vec<&str>> = vec![Vec::new(); num_faces];
+        for selection in self.selections.iter() {
+            for i in selection.faces.iter() {
+                if let Some(names) = face_selections.get_mut(*i as usize) {
+                    names.push(&selection.name);
+                }
+            }
+        }
+        face_selections
+    }
+}
+
+/// How every array of a model was stored, by LOD.
+#[derive(Debug, Default, Clone)]
+pub struct CompressionReport {
+    /// Resolution and arrays of every LOD, in the order of the model.
+    pub lods: Vec<(f32, Vec<CompressedBlock>)>,
+}
+
+impl CompressionReport {
+    /// Same as `LodInfo::compression_summary`, over all LODs.
+    pub fn summary(&self) -> (usize, usize, usize, usize) {
+        let blocks = || self.lods.iter().flat_map(|(_, blocks)| blocks.iter());
+        (
+            blocks().count(),
+            blocks().filter(|b| b.compressed).count(),
+            blocks().map(|b| b.stored_size).sum(),
+            blocks().map(|b| b.size).sum(),
+        )
+    }
+}
+
+/// A LOD in the JSON reports, its resolution and type followed by one more field.
+struct LodEntry<'a, T: ?Sized>(f32, &'static str, &'a T);
+
+impl<'a, T: Serialize + ?Sized> Serialize for LodEntry<'a, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let LodEntry(resolution, name, value) = *self;
+        let mut json = serializer.serialize_struct("LodEntry", 3)?;
+        json.serialize_field("resolution", &resolution)?;
+        json.serialize_field("type", &LodType::from_resolution(resolution).to_string())?;
+        json.serialize_field(name, value)?;
+        json.end()
+    }
+}
+
+impl Serialize for CompressionReport {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let lods: Vec<LodEntry<[CompressedBlock]>> = self.lods.iter()
+            .map(|(resolution, blocks)| LodEntry(*resolution, "arrays", &blocks[..]))
+            .collect();
+
+        let (num_blocks, num_compressed, stored_size, size) = self.summary();
+        let mut json = serializer.serialize_struct("CompressionReport", 5)?;
+        json.serialize_field("num_arrays", &num_blocks)?;
+        json.serialize_field("num_compressed", &num_compressed)?;
+        json.serialize_field("stored_size", &stored_size)?;
+        json.serialize_field("size", &size)?;
+        json.serialize_field("lods", &lods)?;
+        json.end()
+    }
+}
+
+/// One line per array, grouped by LOD.
+impl fmt::Display for CompressionReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (resolution, blocks) in self.lods.iter() {
+            writeln!(f, "{}:", LodType::from_resolution(*resolution))?;
+            for block in blocks.iter() {
+                writeln!(f, "  0x{:08x}  {:<4} {:>10} -> {:>10} bytes",
+                    block.offset,
+                    if block.compressed { "lzo" } else { "raw" },
+                    block.stored_size,
+                    block.size)?;
+            }
+        }
+
+        let (num_blocks, num_compressed, stored_size, size) = self.summary();
+        writeln!(f, "{}/{} arrays compressed ({} -> {} bytes)", num_compressed, num_blocks, stored_size, size)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SelectionSize {
+    pub name: String,
+    pub num_faces: usize,
+    pub num_points: usize,
+}
+
+/// Named selections of a model with their sizes, by LOD.
+#[derive(Debug, Default, Clone)]
+pub struct SelectionReport {
+    /// Resolution of every LOD, in the order of the model, with its selections.
+    pub lods: Vec<(f32, Vec<SelectionSize>)>,
+}
+
+impl Serialize for SelectionReport {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let lods: Vec<LodEntry<[SelectionSize]>> = self.lods.iter()
+            .map(|(resolution, selections)| LodEntry(*resolution, "selections", &selections[..]))
+            .collect();
+
+        let mut json = serializer.serialize_struct("SelectionReport", 1)?;
+        json.serialize_field("lods", &lods)?;
+        json.end()
+    }
+}
+
+/// One line per selection, grouped by LOD.
+impl fmt::Display for SelectionReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (resolution, selections) in self.lods.iter() {
+            writeln!(f, "{}:", LodType::from_resolution(*resolution))?;
+            for s in selections.iter() {
+                writeln!(f, "  {:<32} {:>8} faces {:>8} points", s.name, s.num_faces, s.num_points)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct ParsedModel {
+    pub header: OdolHeader,
+    pub mlod: P3D,
+    pub lods: Vec<LodInfo>,
+}
+
+impl ParsedModel {
+    pub fn compression_report(&self) -> CompressionReport {
+        CompressionReport {
+            lods: self.mlod.lods.iter().zip(self.lods.iter())
+                .map(|(lod, info)| (lod.resolution, info.blocks.clone()))
+                .collect(),
+        }
+    }
+
+    pub fn selection_report(&self) -> SelectionReport {
+        SelectionReport {
+            lods: self.mlod.lods.iter().zip(self.lods.iter())
+                .map(|(lod, info)| {
+                    let selections = info.selections.iter()
+                        .map(|s| SelectionSize {
+                            name: s.name.clone(),
+                            num_faces: s.faces.len(),
+                            num_points: s.points.len(),
+                        })
+                        .collect();
+                    (lod.resolution, selections)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<&[u8]> for ParsedModel {
+    type Error = CrowbarError;
+
+    /// Reads a model with the default `ReadOptions`.
+    fn try_from(data: &[u8]) -> Result<ParsedModel, CrowbarError> {
+        read_odol_from_reader(&mut Cursor::new(data), &ReadOptions::default())
+    }
+}
+
+#[cfg(not(feature = "wasm"))]
+impl TryFrom<File> for ParsedModel {
+    type Error = CrowbarError;
+
+    /// Reads a model with the default `ReadOptions`.
+    fn try_from(file: File) -> Result<ParsedModel, CrowbarError> {
+        read_odol_from_reader(&mut BufReader::new(file), &ReadOptions::default())
+    }
+}
+
+impl OdolHeader {
+    /// `geometry_center` in the coordinates of the points after reading. Compare it to the
+    /// center of mass of a converted model to check that it didn't move.
+    pub fn geometry_center_in(&self, options: &ReadOptions) -> (f32, f32, f32) {
+        if options.skip_bounding_offset {
+            self.geometry_center
+        } else {
+            let (c, b) = (self.geometry_center, self.bounding_center);
+            (c.0 + b.0, c.1 + b.1, c.2 + b.2)
+        }
+    }
+
+    /// Value of the map named property for the map type, if it is a known one.
+    pub fn map_type_name(&self) -> Option<&'static str> {
+        MAP_TYPES.get(self.map_type as usize).copied()
+    }
+}
+
+impl Serialize for OdolHeader {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut json = serializer.serialize_struct("OdolHeader", 36)?;
+        json.serialize_field("version", &self.version)?;
+        json.serialize_field("appid", &self.appid)?;
+        json.serialize_field("game", &self.game.to_string())?;
+        json.serialize_field("view_density", &self.view_density)?;
+        json.serialize_field("lod_density_coef", &self.lod_density_coef)?;
+        json.serialize_field("draw_importance", &self.draw_importance)?;
+        json.serialize_field("shadow_offset", &self.shadow_offset)?;
+        json.serialize_field("point_flags", &self.point_flags)?;
+        json.serialize_field("offset_1", &self.offset_1)?;
+        json.serialize_field("bounding_center", &self.bounding_center)?;
+        json.serialize_field("geometry_center", &self.geometry_center)?;
+        json.serialize_field("cog_offset", &self.cog_offset)?;
+        json.serialize_field("inv_inertia", &self.inv_inertia)?;
+        json.serialize_field("autocenter", &self.autocenter)?;
+        json.serialize_field("lock_autocenter", &self.lock_autocenter)?;
+        json.serialize_field("can_occlude", &self.can_occlude)?;
+        json.serialize_field("can_be_occluded", &self.can_be_occluded)?;
+        json.serialize_field("ai_cover", &self.ai_cover)?;
+        json.serialize_field("force_not_alpha", &self.force_not_alpha)?;
+        json.serialize_field("prefer_shadow_volume", &self.prefer_shadow_volume)?;
+        json.serialize_field("animated", &self.animated)?;
+        json.serialize_field("skeleton_ht_min", &self.skeleton_ht_min)?;
+        json.serialize_field("skeleton_ht_max", &self.skeleton_ht_max)?;
+        json.serialize_field("skeleton_af_max", &self.skeleton_af_max)?;
+        json.serialize_field("skeleton_mf_max", &self.skeleton_mf_max)?;
+        json.serialize_field("skeleton_mf_act", &self.skeleton_mf_act)?;
+        json.serialize_field("skeleton_t_body", &self.skeleton_t_body)?;
+        json.serialize_field("sb_source", &self.sb_source)?;
+        json.serialize_field("min_shadow", &self.min_shadow)?;
+        json.serialize_field("can_blend", &self.can_blend)?;
+        json.serialize_field("map_type", &self.map_type)?;
+        json.serialize_field("map_type_name", &self.map_type_name())?;
+        json.serialize_field("map_icon_color", &self.map_icon_color)?;
+        json.serialize_field("map_selected_color", &self.map_selected_color)?;
+        json.serialize_field("class_type", &self.class_type)?;
+        json.serialize_field("destruct_type", &self.destruct_type)?;
+        json.end()
+    }
+}
+
+/// A LOD in the JSON of a model.
+struct LodJson<'a>(&'a LOD, &'a LodInfo);
+
+/// Totals of `LodInfo::compression_summary` in the JSON of a LOD.
+#[derive(Serialize)]
+struct CompressionSummary {
+    num_arrays: usize,
+    num_compressed: usize,
+    stored_size: usize,
+    size: usize,
+}
+
+impl<'a> Serialize for LodJson<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let LodJson(lod, info) = *self;
+        let properties: Map<String, Value> = info.properties.iter()
+            .map(|(name, value)| (name.clone(), Value::from(value.as_str())))
+            .collect();
+        let (num_arrays, num_compressed, stored_size, size) = info.compression_summary();
+
+        let mut json = serializer.serialize_struct("Lod", 9)?;
+        json.serialize_field("resolution", &lod.resolution)?;
+        json.serialize_field("type", &LodType::from_resolution(lod.resolution).to_string())?;
+        json.serialize_field("num_points", &lod.points.len())?;
+        json.serialize_field("num_faces", &lod.faces.len())?;
+        json.serialize_field("num_uv_sets", &(1 + info.uv_sets.len()))?;
+        json.serialize_field("properties", &properties)?;
+        json.serialize_field("compression", &CompressionSummary { num_arrays, num_compressed, stored_size, size })?;
+        json.serialize_field("diagnostics", &info.diagnostics)?;
+        json.serialize_field("materials", &info.materials)?;
+        json.end()
+    }
+}
+
+impl Serialize for ParsedModel {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let lods: Vec<LodJson> = self.mlod.lods.iter().zip(self.lods.iter())
+            .map(|(lod, info)| LodJson(lod, info))
+            .collect();
+
+        let mut json = serializer.serialize_struct("ParsedModel", 3)?;
+        json.serialize_field("header", &self.header)?;
+        json.serialize_field("lods", &lods)?;
+        json.serialize_field("warnings", &self.header.warnings)?;
+        json.end()
+    }
+}
+
+/// A short overview of the model, one line for the model and one for every LOD.
+impl fmt::Display for ParsedModel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "ODOL version {}, {} LODs", self.header.version, self.mlod.lods.len())?;
+        for (i, lod) in self.mlod.lods.iter().enumerate() {
+            writeln!(f, "  {:>3}  {:<32} {:>8} points {:>8} faces",
+                i,
+                LodType::from_resolution(lod.resolution).to_string(),
+                lod.points.len(),
+                lod.faces.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// Best ratio LZO can compress to, which bounds how large a compressed array can claim to be.
+const LZO_MAX_RATIO: u64 = 255;
+
+/// Fails once `deadline` has passed, with the offset reading stopped at.
+fn check_deadline(deadline: Option<Instant>, offset: u64) -> Result<(), CrowbarError> {
+    match deadline {
+        Some(deadline) if Instant::now() > deadline => {
+            Err(CrowbarError::Timeout(format!("Reading took too long, stopped at 0x{:x}.", offset)))
+        },
+        _ => Ok(()),
+    }
+}
+
+/// Checks that at least `size` more bytes follow in the stream, so that corrupt counts fail
+/// instead of attempting huge allocations.
+fn check_remaining<I: Seek>(reader: &mut I, size: u64, what: &str) -> Result<(), CrowbarError> {
+    let position = reader.stream_position()?;
+    let end = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(position))?;
+
+    let remaining = end.saturating_sub(position);
+    if size > remaining {
+        return Err(CrowbarError::Malformed(format!("{} need at least {} bytes at 0x{:x}, but only {} are left.", what, size, position, remaining)));
+    }
+
+    Ok(())
+}
+
+pub fn read_compressed_array<I: Read + Seek>(reader: &mut I, output_size: usize) -> Result<Vec<u8>, CrowbarError> {
+    read_compressed_block(reader, output_size).map(|(data, _)| data)
+}
+
+/// Same as `read_compressed_array`, but also returns how the array was stored.
+pub fn read_compressed_block<I: Read + Seek>(reader: &mut I, output_size: usize) -> Result<(Vec<u8>, CompressedBlock), CrowbarError> {
+    let mut output = Vec::new();
+    let block = read_compressed_into(reader, output_size, &mut output, Lzo::default().backend(), None)?;
+    Ok((output, block))
+}
+
+/// Same as `read_compressed_block`, but replaces the contents of `output` with the array, so
+/// that raw arrays reuse its allocation.
+fn read_compressed_into<I: Read + Seek>(reader: &mut I, output_size: usize, output: &mut Vec<u8>, backend: &dyn LzoBackend, deadline: Option<Instant>) -> Result<CompressedBlock, CrowbarError> {
+    // 0 is a raw array and 2 an LZO-compressed one. Older formats used 1 for LZSS, which
+    // binarize doesn't write for the supported versions.
+    let offset = reader.stream_position()?;
+    let comp_type = reader.read_u8()?;
+    match comp_type {
+        0 => {
+            check_remaining(reader, output_size as u64, "Uncompressed array")?;
+            output.clear();
+            output.resize(output_size, 0);
+            reader.read_exact(output)?;
+            return Ok(CompressedBlock { offset, compressed: false, stored_size: output_size, size: output_size });
+        },
+        2 => {},
+        _ => {
+            return Err(CrowbarError::Unsupported(format!("Compression type {} of array at 0x{:x} is not supported.", comp_type, offset)));
+        }
+    }
+
+    check_remaining(reader, output_size as u64 / LZO_MAX_RATIO, "Compressed array")?;
+
+    // The compressed size isn't stored, so read as much as it could possibly be once and
+    // search for the size that decompresses cleanly in memory.
+    let fp = reader.seek(SeekFrom::Current(0))?;
+    let mut buffer: Vec<u8> = Vec::with_capacity(output_size);
+    reader.by_ref().take(output_size as u64).read_to_end(&mut buffer)?;
+
+    let mut size_small = 0;
+    let mut size_large = output_size;
+
+    // TODO: super hacky
+    loop {
+        if size_large < size_small || size_large - size_small <= 1 {
+            return Err(CrowbarError::Malformed(format!("Failed to find the size of an LZO block at 0x{:x}.", fp)));
+        }
+        check_deadline(deadline, fp)?;
+
+        let size = size_small + (size_large - size_small) / 2;
+        trace!("    guessing LZO size: {:?} ({} - {})", size, size_small, size_large);
+
+        if size > buffer.len() {
+            size_large = size;
+            continue;
+        }
+
+        let result = backend.decompress(&buffer[..size], output_size);
+        match result {
+            Ok(decomp) => {
+                // Catch a wrong guess here, instead of wherever the short array is used.
+                if decomp.len() != output_size {
+                    return Err(CrowbarError::Malformed(format!("LZO block at 0x{:x} decompressed to {} bytes, expected {}.", fp, decomp.len(), output_size)));
+                }
+
+                reader.seek(SeekFrom::Start(fp + size as u64))?;
+                *output = decomp;
+                return Ok(CompressedBlock { offset, compressed: true, stored_size: size, size: output_size });
+            },
+            Err(LzoError::InputOverrun) => {
+                size_small = size;
+            },
+            Err(LzoError::InputNotConsumed) => {
+                size_large = size;
+            },
+            Err(e) => {
+                return Err(CrowbarError::Malformed(format!("Failed to decompress LZO block at 0x{:x}: {}", fp, e)));
+            }
+        }
+    }
+}
+
+/// Moves past an array of `output_size` decompressed bytes without decompressing it, for
+/// tools that only need to reach what follows. Returns the number of bytes skipped, including
+/// the compression type. Only the compressed bytes of an LZO array are read to find its end.
+pub fn skip_compressed_array<I: Read + Seek>(reader: &mut I, output_size: usize) -> Result<u64, CrowbarError> {
+    let offset = reader.stream_position()?;
+    let comp_type = reader.read_u8()?;
+    let size = match comp_type {
+        0 => {
+            check_remaining(reader, output_size as u64, "Uncompressed array")?;
+            output_size
+        },
+        2 => {
+            check_remaining(reader, output_size as u64 / LZO_MAX_RATIO, "Compressed array")?;
+            let mut buffer: Vec<u8> = Vec::with_capacity(output_size);
+            reader.by_ref().take(output_size as u64).read_to_end(&mut buffer)?;
+            lzo::compressed_size(&buffer, output_size).map_err(|e| {
+                CrowbarError::Malformed(format!("Failed to find the end of the LZO block at 0x{:x}: {}", offset + 1, e))
+            })?
+        },
+        _ => {
+            return Err(CrowbarError::Unsupported(format!("Compression type {} of array at 0x{:x} is not supported.", comp_type, offset)));
+        }
+    };
+
+    reader.seek(SeekFrom::Start(offset + 1 + size as u64))?;
+    Ok(1 + size as u64)
+}
+
+/// Presents arrays read with `load` as a stream of their decompressed bytes. Every array
+/// replaces what is left of the previous one, so one reader can serve all arrays of a LOD
+/// without a new buffer and cursor for each.
+#[derive(Debug, Default)]
+pub struct ArrayReader {
+    buffer: Vec<u8>,
+    position: usize,
+    lzo: Lzo,
+    deadline: Option<Instant>,
+}
+
+impl ArrayReader {
+    pub fn new(lzo: Lzo) -> ArrayReader {
+        ArrayReader { lzo, ..Default::default() }
+    }
+
+    /// Reads the next array of `size` decompressed bytes from `reader` and returns how it
+    /// was stored.
+    pub fn load<I: Read + Seek>(&mut self, reader: &mut I, size: usize) -> Result<CompressedBlock, CrowbarError> {
+        let block = read_compressed_into(reader, size, &mut self.buffer, self.lzo.backend(), self.deadline)?;
+        self.position = 0;
+        Ok(block)
+    }
+
+    /// Returns the rest of the current array and moves past it.
+    pub fn take_rest(&mut self) -> Vec<u8> {
+        let rest = self.buffer[self.position..].to_vec();
+        self.position = self.buffer.len();
+        rest
+    }
+}
+
+impl Read for ArrayReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = (&self.buffer[self.position..]).read(buf)?;
+        self.position += read;
+        Ok(read)
+    }
+}
+
+/// Maps the clip flags binarize stores for a point to the point flags of MLOD, as Object
+/// Builder shows them. Lighting bits don't map back unambiguously and are left out, hidden
+/// points aren't marked in the ODOL at all.
+fn mlod_point_flags(clip_flags: u32) -> u32 {
+    // on, under, above and keep land
+    ((clip_flags & 0xf00) >> 8)
+        // decal and vertical decal
+        | ((clip_flags & 0x3000) >> 4)
+        // no fog and sky fog
+        | ((clip_flags & 0xc000) >> 2)
+        // user value
+        | ((clip_flags & 0xff0_0000) >> 4)
+}
+
+/// Reads the UVs of a set after its count. Sets without UVs, like those of LODs without
+/// textures, store nothing else.
+fn read_uvs<I: Read + Seek>(reader: &mut I, num_uvs: u32, uv_scale: (f32, f32, f32, f32), arrays: &mut ArrayReader, blocks: &mut Vec<CompressedBlock>) -> Result<Vec<(f32, f32)>, CrowbarError> {
+    if num_uvs == 0 {
+        return Ok(Vec::new());
+    }
+
+    let uv_range: (f32, f32) = (uv_scale.2 - uv_scale.0, uv_scale.3 - uv_scale.1);
+
+    // Constant UVs aren't stored, but there can't be more of them than points, which are.
+    check_remaining(reader, num_uvs as u64 * 12 / LZO_MAX_RATIO, "UVs")?;
+
+    let mut uvs: Vec<(f32, f32)> = Vec::with_capacity(num_uvs as usize);
+    let fill = reader.read_u8()?;
+    if fill == 1 {
+        let u: f32 = ((reader.read_i16::<LittleEndian>()? as i32 + 0x7fff) as f32) / ((2 * 0x7fff) as f32);
+        let v: f32 = ((reader.read_i16::<LittleEndian>()? as i32 + 0x7fff) as f32) / ((2 * 0x7fff) as f32);
+        let uv: (f32, f32) = (u * uv_range.0 + uv_scale.0, v * uv_range.1 + uv_scale.1);
+        uvs.resize(num_uvs as usize, uv);
+    } else if fill == 0 {
+        blocks.push(arrays.load(reader, num_uvs as usize * 4)?);
+
+        for i in 0..num_uvs {
+            let u: f32 = ((arrays.read_i16::<LittleEndian>()? as i32 + 0x7fff) as f32) / ((2 * 0x7fff) as f32);
+            let v: f32 = ((arrays.read_i16::<LittleEndian>()? as i32 + 0x7fff) as f32) / ((2 * 0x7fff) as f32);
+            let uv: (f32, f32) = (u * uv_range.0 + uv_scale.0, v * uv_range.1 + uv_scale.1);
+            if i < 20 {
+                trace!("    - ({}, {})", uv.0, uv.1);
+            }
+            uvs.push(uv);
+        }
+    } else {
+        let position = reader.stream_position()? - 1;
+        return Err(CrowbarError::Malformed(format!("Unexpected fill value {} of UVs at 0x{:x}, expected 0 or 1.", fill, position)));
+    }
+
+    Ok(uvs)
+}
+
+/// Reads the animations of the model.cfg and the bone moved by each of them. The bone and axis
+/// of every animation are stored again for every resolution, only the first bone is kept.
+fn read_animations<I: Read + Seek>(reader: &mut I, bones: &[(String, String)], options: &ReadOptions) -> Result<Vec<OdolAnimation>, CrowbarError> {
+    let mut animations: Vec<OdolAnimation> = Vec::new();
+    if reader.read_u8()? > 0 {
+        let num_anims = reader.read_u32::<LittleEndian>()?;
+        trace!("  num anims: {}", num_anims);
+        check_remaining(reader, num_anims as u64 * (4 + 1 + 1 + 4*4 + 3*4 + 2*4), "Animations")?;
+        let mut axis_sizes: Vec<i64> = Vec::with_capacity(num_anims as usize);
+        for _i in 0..num_anims {
+            let animtype = reader.read_u32::<LittleEndian>()?;
+            let name = reader.read_cstring()?;
+            trace!("    - {}", name);
+            trace!("      type: 0x{:x}", animtype);
+            match axis_size(animtype) {
+                Some(size) => axis_sizes.push(size),
+                None => return Err(CrowbarError::Unsupported(format!("Animation \"{}\" has unknown type {}.", name, animtype))),
+            }
+            let source = reader.read_cstring()?;
+            trace!("      source: \"{}\"", source);
+            let value = (reader.read_f32::<LittleEndian>()?, reader.read_f32::<LittleEndian>()?);
+            trace!("      value: {:?} - {:?}", value.0, value.1);
+            let phase = (reader.read_f32::<LittleEndian>()?, reader.read_f32::<LittleEndian>()?);
+            trace!("      phase: {:?} - {:?}", phase.0, phase.1);
+            reader.seek(SeekFrom::Current(4))?;
+            //assert_eq!(reader.read_u32::<LittleEndian>()?, 0x38d1b717);
+            let zero = reader.read_u32::<LittleEndian>()?;
+            if zero != 0 {
+                let position = reader.stream_position()? - 4;
+                let msg = format!("Animation \"{}\" has 0x{:x} at 0x{:x}, expected 0.", name, zero, position);
+                if options.strict {
+                    return Err(CrowbarError::Malformed(msg));
+                }
+                trace!("      {}", msg);
+            }
+            let source_address = reader.read_u32::<LittleEndian>()?;
+            trace!("      source address: {}", source_address);
+
+            // Direct animations keep their axis position and direction, angle and offset along
+            // the axis here instead of per resolution.
+            let num_params = if animtype == 8 { 8 } else { 2 };
+            let mut params = Vec::with_capacity(num_params);
+            for _j in 0..num_params {
+                params.push(reader.read_f32::<LittleEndian>()?);
+            }
+
+            if animtype <= 3 {
+                trace!("      angle: {:?} - {:?}", params[0], params[1]);
+            } else if animtype <= 7 {
+                trace!("      offset: {:?} - {:?}", params[0], params[1]);
+            } else if animtype != 8 {
+                trace!("      hide: {:?}", params[0]);
+                trace!("      unhide: {:?}", params[1]);
+            }
+
+            animations.push(OdolAnimation {
+                name,
+                anim_type: animtype,
+                source,
+                value,
+                phase,
+                source_address,
+                params,
+                bone: None,
+            });
+        }
+
+        // Per resolution, the animations of every bone, then the bone and axis of every
+        // animation.
+        let num_resolutions = reader.read_u32::<LittleEndian>()?;
+        trace!("  num resolutions: {}", num_resolutions);
+        for _i in 0..num_resolutions {
+            let num_bones = reader.read_u32::<LittleEndian>()?;
+            check_remaining(reader, num_bones as u64 * 4, "Bone animations")?;
+            for _j in 0..num_bones {
+                let num_bone_anims = reader.read_u32::<LittleEndian>()?;
+                reader.seek(SeekFrom::Current(num_bone_anims as i64 * 4))?;
+            }
+        }
+        for _i in 0..num_resolutions {
+            for (animation, axis_size) in animations.iter_mut().zip(axis_sizes.iter()) {
+                let bone_name_index = reader.read_i32::<LittleEndian>()?;
+                if bone_name_index == -1 {
+                    continue;
+                }
+                if animation.bone.is_none() {
+                    animation.bone = bones.get(bone_name_index as usize).map(|(name, _)| name.clone());
+                }
+
+                reader.seek(SeekFrom::Current(*axis_size))?;
+            }
+        }
+    }
+
+    Ok(animations)
+}
+
+/// Traces the offset at which a section of the file starts, so that the verbose output
+/// doubles as a map of the file.
+fn trace_section<I: Seek>(reader: &mut I, section: &str) -> Result<(), CrowbarError> {
+    if verbose() {
+        eprintln!("[0x{:08x}] {}", reader.stream_position()?, section);
+    }
+    Ok(())
+}
+
+/// Reads the start of a LOD up to its textures and materials, which come before any geometry.
+/// Returns the number of points, the texture paths and the materials.
+fn read_lod_paths<I: Read + Seek>(reader: &mut I) -> Result<(u32, Vec<String>, Vec<Material>), CrowbarError> {
+    trace_section(reader, "proxies")?;
+    let num_proxies = reader.read_u32::<LittleEndian>()?;
+    trace!("  num proxies: {}", num_proxies);
+    for _i in 0..num_proxies {
+        let name = reader.read_cstring()?;
+        trace!("    - {}", name);
+        reader.read_matrix4x3()?;
+        reader.seek(SeekFrom::Current(4*4))?;
+    }
+
+    let num_bones_subskeleton = reader.read_u32::<LittleEndian>()?;
+    trace!("  num bones subskeleton: {}", num_bones_subskeleton);
+    reader.seek(SeekFrom::Current(num_bones_subskeleton as i64 * 4))?;
+
+    let num_bones_skeleton = reader.read_u32::<LittleEndian>()?;
+    trace!("  num bones skeleton: {}", num_bones_skeleton);
+    for _i in 0..num_bones_skeleton {
+        let num_links = reader.read_u32::<LittleEndian>()?;
+        reader.seek(SeekFrom::Current(num_links as i64 * 4))?;
+    }
+
+    let num_points = reader.read_u32::<LittleEndian>()?;
+    trace!("  num points: {}", num_points);
+    check_remaining(reader, num_points as u64 * 12 / LZO_MAX_RATIO, "Points")?;
+
+    reader.seek(SeekFrom::Current(3*4 + 3*12 + 4))?;
+
+    trace_section(reader, "textures")?;
+    let num_textures = reader.read_u32::<LittleEndian>()?;
+    trace!("  num textures: {}", num_textures);
+    check_remaining(reader, num_textures as u64, "Textures")?;
+    let mut textures: Vec<String> = Vec::with_capacity(num_textures as usize);
+    for _i in 0..num_textures {
+        let texture = reader.read_cstring()?;
+        trace!("    - {}", texture);
+        textures.push(texture);
+    }
+
+    trace_section(reader, "materials")?;
+    let num_materials = reader.read_u32::<LittleEndian>()?;
+    trace!("  num materials: {}", num_materials);
+    check_remaining(reader, num_materials as u64 * (1 + 4 + 6*16 + 5*4 + 1 + 2*4 + 2*4 + 4 + 1 + 4 + 1), "Materials")?;
+    let mut materials: Vec<Material> = Vec::with_capacity(num_materials as usize);
+    for _i in 0..num_materials {
+        let path = reader.read_cstring()?;
+        trace!("    - {}", path);
+
+        reader.seek(SeekFrom::Current(4 + 6*16 + 5*4))?;
+
+        let surface = reader.read_cstring()?;
+        trace!("      surface: \"{}\"", surface);
+
+        reader.seek(SeekFrom::Current(2*4))?;
+        let num_stages = reader.read_u32::<LittleEndian>()?;
+        trace!("      num stages: {}", num_stages);
+        let num_transforms = reader.read_u32::<LittleEndian>()?;
+        trace!("      num transforms: {}", num_transforms);
+
+        // The transforms come after the stages that reference them by index.
+        let mut stages: Vec<(MaterialStage, u32)> = Vec::with_capacity(num_stages as usize + 1);
+        for _j in 0..num_stages {
+            let filter = reader.read_u32::<LittleEndian>()?;
+            let texture = reader.read_cstring()?;
+            trace!("        - {}", texture);
+            let transform_index = reader.read_u32::<LittleEndian>()?;
+            reader.seek(SeekFrom::Current(1))?;
+            stages.push((MaterialStage { texture, filter, transform: None }, transform_index));
+        }
+
+        check_remaining(reader, num_transforms as u64 * (4 + 3*4*4), "Material transforms")?;
+        let mut transforms = Vec::with_capacity(num_transforms as usize);
+        for _j in 0..num_transforms {
+            let uv_source = reader.read_u32::<LittleEndian>()?;
+            let matrix = reader.read_matrix4x3()?;
+            transforms.push(StageTransform { uv_source, matrix });
+        }
+
+        let filter = reader.read_u32::<LittleEndian>()?;
+        let texture = reader.read_cstring()?;
+        let transform_index = reader.read_u32::<LittleEndian>()?;
+        reader.seek(SeekFrom::Current(1))?;
+        stages.push((MaterialStage { texture, filter, transform: None }, transform_index));
+
+        materials.push(Material {
+            path,
+            surface,
+            stages: stages.into_iter().map(|(mut stage, transform_index)| {
+                stage.transform = transforms.get(transform_index as usize).copied();
+                stage
+            }).collect(),
+        });
+    }
+
+    Ok((num_points, textures, materials))
+}
+
+/// Fails with `msg`, unless validating or repairing, where it is only collected.
+fn violation(info: &mut LodInfo, options: &ReadOptions, msg: String) -> Result<(), CrowbarError> {
+    if options.strict || (!options.validate && !options.repair) {
+        return Err(CrowbarError::Malformed(msg));
+    }
+
+    info.diagnostics.push(msg);
+    Ok(())
+}
+
+/// Collects `msg` about something that can be read past, or fails with it when strict.
+fn oddity(info: &mut LodInfo, options: &ReadOptions, msg: String) -> Result<(), CrowbarError> {
+    if options.strict {
+        return Err(CrowbarError::Malformed(msg));
+    }
+
+    info.diagnostics.push(msg);
+    Ok(())
+}
+
+/// Counts `value` if it isn't finite, and replaces it with 0 when sanitizing floats.
+fn sanitize_float(value: &mut f32, options: &ReadOptions, count: &mut usize) {
+    if !value.is_finite() {
+        *count += 1;
+        if options.sanitize_floats {
+            *value = 0.0;
+        }
+    }
+}
+
+fn sanitize_vec3(value: &mut (f32, f32, f32), options: &ReadOptions, count: &mut usize) {
+    sanitize_float(&mut value.0, options, count);
+    sanitize_float(&mut value.1, options, count);
+    sanitize_float(&mut value.2, options, count);
+}
+
+fn non_finite_message(count: usize, what: &str, options: &ReadOptions) -> String {
+    let replaced = if options.sanitize_floats { ", replaced with 0" } else { "" };
+    format!("{} non-finite floats in {}{}.", count, what, replaced)
+}
+
+/// Collects a warning about the header, or fails with it when strict.
+fn header_warning(warnings: &mut Vec<String>, options: &ReadOptions, warning: String) -> Result<(), CrowbarError> {
+    if options.strict {
+        return Err(CrowbarError::Malformed(warning));
+    }
+
+    warnings.push(warning);
+    Ok(())
+}
+
+/// Reads the part of a LOD between the paths and the faces, returns the number of faces.
+fn read_num_faces<I: Read + Seek>(reader: &mut I) -> Result<u32, CrowbarError> {
+    trace_section(reader, "edges")?;
+    let num_edges1 = reader.read_u32::<LittleEndian>()?;
+    trace!("  num edges 1: {}", num_edges1);
+    reader.seek(SeekFrom::Current(2 * num_edges1 as i64))?;
+
+    let num_edges2 = reader.read_u32::<LittleEndian>()?;
+    trace!("  num edges 2: {}", num_edges2);
+    reader.seek(SeekFrom::Current(2 * num_edges2 as i64))?;
+
+    trace_section(reader, "faces")?;
+    let num_faces = reader.read_u32::<LittleEndian>()?;
+    trace!("  num faces: {}", num_faces);
+    Ok(num_faces)
+}
+
+fn read_lod<I: Read + Seek>(reader: &mut I, lod: &mut LOD, info: &mut LodInfo, bounding_center: (f32, f32, f32), options: &ReadOptions, deadline: Option<Instant>) -> Result<(), CrowbarError> {
+    let mut timer = Timer::start(|| format!("LOD {}", LodType::from_resolution(lod.resolution)));
+    let mut arrays = ArrayReader::new(options.lzo);
+    arrays.deadline = deadline;
+    let (num_points, textures, materials) = read_lod_paths(reader)?;
+    let num_faces = read_num_faces(reader)?;
+    reader.seek(SeekFrom::Current(6))?;
+    check_remaining(reader, num_faces as u64 * (1 + 3*4), "Faces")?;
+    let mut faces: Vec<(Vec<u32>, usize, usize)> = Vec::with_capacity(num_faces as usize);
+    // Faces that aren't triangles or quads stay in the list, since sections reference faces
+    // by their offset, and are left out of the LOD with those referencing missing points.
+    let mut degenerate: Vec<bool> = Vec::with_capacity(num_faces as usize);
+    for i in 0..num_faces {
+        let face_type = reader.read_u8()?;
+        let mut face: Vec<u32> = Vec::with_capacity(face_type as usize);
+        for _j in 0..face_type {
+            face.push(reader.read_u32::<LittleEndian>()?);
+        }
+        //trace!("    {}: {:?}", face_type, face);
+
+        let is_degenerate = face_type != 3 && face_type != 4;
+        if is_degenerate {
+            let position = reader.stream_position()? - 1 - 4 * face_type as u64;
+            let msg = format!("Face {} of LOD {} at 0x{:x} has {} vertices, expected 3 or 4.", i, lod.resolution, position, face_type);
+            if options.keep_going && !options.validate && !options.repair && !options.strict {
+                info.diagnostics.push(format!("{} The face was left out.", msg));
+            } else {
+                violation(info, options, msg)?;
+            }
+        }
+        degenerate.push(is_degenerate);
+        faces.push((face, 0xffffff, 0xffffff));
+    }
+
+    // Sections reference faces by their byte offset in the face data, which is converted to
+    // face indices right away.
+    let mut face_offsets: Vec<u32> = Vec::with_capacity(faces.len());
+    let mut faces_size: u32 = 0;
+    for face in faces.iter() {
+        face_offsets.push(faces_size);
+        faces_size += face.0.len() as u32 * 4 + 1;
+    }
+
+    trace_section(reader, "sections")?;
+    let num_sections = reader.read_u32::<LittleEndian>()?;
+    trace!("  num sections: {}", num_sections);
+    check_remaining(reader, num_sections as u64 * (2*4 + 3*4 + 2 + 4 + 4 + 4 + 4), "Sections")?;
+    let mut sections: Vec<(u32, u32)> = Vec::with_capacity(num_sections as usize);
+    for _i in 0..num_sections {
+        let face_from = reader.read_u32::<LittleEndian>()?;
+        let face_to = reader.read_u32::<LittleEndian>()?;
+        trace!("    - {} - {}", face_from, face_to);
+        if face_from > face_to || face_to > faces_size {
+            oddity(info, options, format!("Section 0x{:x} - 0x{:x} is out of the 0x{:x} bytes of faces.", face_from, face_to, faces_size))?;
+        }
+        let first_face = face_offsets.partition_point(|o| *o < face_from);
+        let end_face = face_offsets.partition_point(|o| *o < face_to).max(first_face);
+        sections.push((first_face as u32, end_face as u32));
+
+        reader.seek(SeekFrom::Current(3*4))?;
+
+        let texture_index = reader.read_u16::<LittleEndian>()?;
+        trace!("      texture index: {}", texture_index);
+
+        reader.seek(SeekFrom::Current(4))?;
+
+        let material_index = reader.read_i32::<LittleEndian>()?;
+        trace!("      material index: {}", material_index);
+        if material_index == -1 {
+            reader.seek(SeekFrom::Current(1))?;
+        }
+
+        let num_stages = reader.read_u32::<LittleEndian>()?;
+        trace!("      num stages: {}", num_stages);
+        reader.seek(SeekFrom::Current(4 * num_stages as i64))?;
+
+        let coll_info = reader.read_u32::<LittleEndian>()?;
+        trace!("      coll info: {}", coll_info);
+        if coll_info > 0 {
+            reader.seek(SeekFrom::Current(2*12 + 4 + 12 + 4))?;
+        }
+
+        for face in faces[first_face..end_face].iter_mut() {
+            face.1 = texture_index as usize;
+            face.2 = material_index as usize;
+        }
+    }
+
+    timer.lap("faces");
+
+    // TODO: handle selections properly
+    trace_section(reader, "selections")?;
+    let num_selections = reader.read_u32::<LittleEndian>()?;
+    trace!("  num selections: {}", num_selections);
+    check_remaining(reader, num_selections as u64 * (1 + 4 + 4 + 1 + 3*4), "Selections")?;
+    let mut selections: Vec<(String, Vec<u32>, Vec<u32>, Vec<u32>, Vec<u8>)> = Vec::with_capacity(num_selections as usize);
+    for _i in 0..num_selections {
+        let name = reader.read_cstring()?;
+        trace!("    - {}", name);
+
+        let num_f = reader.read_u32::<LittleEndian>()?;
+        trace!("      num faces: {}", num_f);
+        let faces: Vec<u32> = if num_f > 0 {
+            info.blocks.push(arrays.load(reader, num_f as usize * 4)?);
+            let mut faces = Vec::with_capacity(num_f as usize);
+            for _j in 0..num_f {
+                faces.push(arrays.read_u32::<LittleEndian>()?);
+            }
+            faces
+        } else {
+            Vec::new()
+        };
+
+        let c = reader.read_u32::<LittleEndian>()?;
+        reader.seek(SeekFrom::Current(c as i64 * 4))?;
+
+        reader.seek(SeekFrom::Current(1))?;
+
+        let num_s = reader.read_u32::<LittleEndian>()?;
+        trace!("      num sections: {}", num_s);
+        let sections: Vec<u32> = if num_s > 0 {
+            info.blocks.push(arrays.load(reader, num_s as usize * 4)?);
+            let mut sections = Vec::with_capacity(num_s as usize);
+            for _j in 0..num_s {
+                sections.push(arrays.read_u32::<LittleEndian>()?);
+            }
+            sections
+        } else {
+            Vec::new()
+        };
+
+        let num_v = reader.read_u32::<LittleEndian>()?;
+        trace!("      num vertices: {}", num_v);
+        let verts: Vec<u32> = if num_v > 0 {
+            info.blocks.push(arrays.load(reader, num_v as usize * 4)?);
+            let mut verts = Vec::with_capacity(num_v as usize);
+            for _j in 0..num_v {
+                verts.push(arrays.read_u32::<LittleEndian>()?);
+            }
+            verts
+        } else {
+            Vec::new()
+        };
+
+        let num_w = reader.read_u32::<LittleEndian>()?;
+        let vertweights: Vec<u8> = if num_w > 0 {
+            info.blocks.push(arrays.load(reader, num_w as usize)?);
+            arrays.take_rest()
+        } else {
+            Vec::new()
+        };
+
+        selections.push((name, faces, sections, verts, vertweights));
+    }
+
+    trace_section(reader, "properties")?;
+    let num_properties = reader.read_u32::<LittleEndian>()?;
+    trace!("  num properties: {}", num_properties);
+    for _i in 0..num_properties {
+        let name = reader.read_cstring()?;
+        let value = reader.read_cstring()?;
+        trace!("    - {} = \"{}\"", name, value);
+        info.properties.push((name, value));
+    }
+
+    // Keyframes store per-bone positions, which MLOD's per-point #Animation# taggs can't
+    // represent, so they are skipped.
+    trace_section(reader, "keyframes")?;
+    let num_frames = reader.read_u32::<LittleEndian>()?;
+    trace!("  num frames: {}", num_frames);
+    if num_frames > 0 {
+        oddity(info, options, format!("{} keyframes were skipped.", num_frames))?;
+    }
+    for _i in 0..num_frames {
+        let time = reader.read_f32::<LittleEndian>()?;
+        let num_frame_bones = reader.read_u32::<LittleEndian>()?;
+        trace!("    - {} ({} bones)", time, num_frame_bones);
+        reader.seek(SeekFrom::Current(num_frame_bones as i64 * 12))?;
+    }
+
+    reader.seek(SeekFrom::Current(3*4 + 1 + 4))?;
+
+    // Binarize stores clip flags per point here, whose bits are mapped back to the point
+    // flags of MLOD once the points are read.
+    trace_section(reader, "point flags")?;
+    let num_pointflags = reader.read_u32::<LittleEndian>()?;
+    trace!("  num pointflags: {}", num_pointflags);
+    let comp_type = reader.read_u8()?;
+    let mut clip_fill = 0;
+    let mut clip_flags: Vec<u32> = Vec::new();
+    if comp_type == 1 {
+        clip_fill = reader.read_u32::<LittleEndian>()?;
+        trace!("  fill: {:x}", clip_fill);
+    } else if comp_type == 0 {
+        check_remaining(reader, num_pointflags as u64 * 4, "point flags")?;
+        clip_flags.reserve(num_pointflags as usize);
+        for _i in 0..num_pointflags {
+            clip_flags.push(reader.read_u32::<LittleEndian>()?);
+        }
+    } else {
+        let position = reader.stream_position()? - 1;
+        return Err(CrowbarError::Malformed(format!("Unexpected fill value {} of point flags at 0x{:x}, expected 0 or 1.", comp_type, position)));
+    }
+
+    timer.lap("selections");
+
+    trace_section(reader, "uvs")?;
+
+    let uv_scale: (f32, f32, f32, f32) = (
+        reader.read_f32::<LittleEndian>()?,
+        reader.read_f32::<LittleEndian>()?,
+        reader.read_f32::<LittleEndian>()?,
+        reader.read_f32::<LittleEndian>()?);
+    trace!("  uv scale: ({}, {}, {}, {})", uv_scale.0, uv_scale.1, uv_scale.2, uv_scale.3);
+
+    // TODO: handle UVs properly
+    let num_uvs = reader.read_u32::<LittleEndian>()?;
+    trace!("  num uvs: {}", num_uvs);
+    let mut uvs = read_uvs(reader, num_uvs, uv_scale, &mut arrays, &mut info.blocks)?;
+
+    // The number of sets includes the first one, which is stored before it even when it is
+    // empty. The other sets follow whether or not the first one has UVs.
+    trace_section(reader, "uv sets")?;
+    let num_uvsets = reader.read_u32::<LittleEndian>()?;
+    trace!("  num uv sets: {}", num_uvsets);
+    if num_uvsets == 0 && num_uvs > 0 {
+        oddity(info, options, format!("LOD has {} UVs, but no UV sets.", num_uvs))?;
+    }
+    check_remaining(reader, num_uvsets.saturating_sub(1) as u64 * (4*4 + 4), "UV sets")?;
+    for _i in 1..num_uvsets {
+        let uv_scale: (f32, f32, f32, f32) = (
+            reader.read_f32::<LittleEndian>()?,
+            reader.read_f32::<LittleEndian>()?,
+            reader.read_f32::<LittleEndian>()?,
+            reader.read_f32::<LittleEndian>()?);
+
+        let num_uvs = reader.read_u32::<LittleEndian>()?;
+        trace!("  uv set {}: {} uvs", info.uv_sets.len() + 1, num_uvs);
+
+        info.uv_sets.push(read_uvs(reader, num_uvs, uv_scale, &mut arrays, &mut info.blocks)?);
+    }
+
+    timer.lap("uvs");
+
+    // The point count is stored twice. The second one belongs to the point array that
+    // follows, so go on with that one if they differ.
+    trace_section(reader, "points")?;
+    let num_points_2 = reader.read_u32::<LittleEndian>()?;
+    if num_points_2 != num_points {
+        violation(info, options, format!("Point count {} doesn't match the {} read before.", num_points_2, num_points))?;
+    }
+    let num_points = num_points_2;
+    trace!("  num points: {}", num_points);
+
+    // Faces index UVs by point, so make sure every point has one, even if the LOD stores none.
+    uvs.resize(num_points as usize, (0.0, 0.0));
+    for uv_set in info.uv_sets.iter_mut() {
+        uv_set.resize(num_points as usize, (0.0, 0.0));
+    }
+
+    let mut points: Vec<(f32, f32, f32)> = Vec::with_capacity(num_points as usize);
+    if num_points > 0 {
+        info.blocks.push(arrays.load(reader, num_points as usize * 12)?);
+
+        for i in 0..num_points {
+            let point = (arrays.read_f32::<LittleEndian>()?, arrays.read_f32::<LittleEndian>()?, arrays.read_f32::<LittleEndian>()?);
+            if i < 20 {
+                trace!("    - {:?}", point);
+            }
+            points.push(point);
+        }
+    }
+
+    timer.lap("points");
+
+    let mut non_finite = 0;
+    for p in points.iter_mut() {
+        sanitize_vec3(p, options, &mut non_finite);
+    }
+    for uv in uvs.iter_mut().chain(info.uv_sets.iter_mut().flatten()) {
+        sanitize_float(&mut uv.0, options, &mut non_finite);
+        sanitize_float(&mut uv.1, options, &mut non_finite);
+    }
+    if non_finite > 0 {
+        let msg = non_finite_message(non_finite, "the points and UVs", options);
+        oddity(info, options, msg)?;
+    }
+
+    for (i, p) in points.into_iter().enumerate() {
+        lod.points.push(Point {
+            coords: (
+                p.0 + bounding_center.0,
+                p.1 + bounding_center.1,
+                p.2 + bounding_center.2
+            ),
+            flags: mlod_point_flags(clip_flags.get(i).copied().unwrap_or(clip_fill))
+        });
+        lod.face_normals.push((0.0, 0.0, 0.0)); // TODO
+    }
+
+    // A corrupt point index would only panic once it's used, so check them all up front. With
+    // keep_going or when validating, such faces are left out of the LOD and its selections.
+    let mut skipped: Vec<bool> = Vec::with_capacity(faces.len());
+    for (i, (verts, _, _)) in faces.iter().enumerate() {
+        let bad_index = verts.iter().find(|v| **v >= num_points);
+        if let Some(index) = bad_index {
+            let msg = format!("Face {} of LOD {} references point {}, but there are only {}.", i, lod.resolution, index, num_points);
+            if options.keep_going && !options.validate && !options.repair && !options.strict {
+                info.diagnostics.push(format!("{} The face was left out.", msg));
+            } else {
+                violation(info, options, msg)?;
+            }
+        }
+        skipped.push(bad_index.is_some() || degenerate[i]);
+    }
+
+    // ODOL faces are wound the other way around than MLOD ones. Exporters work on the MLOD
+    // order and can flip it again if needed. With `faithful`, the ODOL order is kept so
+    // that binarizing the MLOD again gives the same faces.
+    for ((verts, t, m), _) in faces.iter().zip(skipped.iter()).filter(|(_, skip)| !**skip) {
+        let mut vertices: Vec<Vertex> = verts.iter().map(|i| Vertex {
+            point_index: *i,
+            normal_index: *i,
+            uv: uvs[*i as usize],
+        }).collect();
+        if !options.faithful {
+            vertices.reverse();
+        }
+
+        lod.faces.push(Face {
+            vertices,
+            flags: 0,
+            texture: textures.get(*t).map(|t| t.clone()).unwrap_or(String::new()),
+            material: materials.get(*m).map(|t| t.path.clone()).unwrap_or(String::new())
+        })
+    }
+    info.materials = materials;
+
+    for (name, selfaces, selsections, selverts, mut selvertweights) in selections {
+        // ODOL weights are linear from 0 to 255 and left out if all are full.
+        if selvertweights.len() == 0 {
+            selvertweights = Vec::with_capacity(selverts.len());
+            selvertweights.resize(selverts.len(), 255);
+        }
+
+        if selverts.len() != selvertweights.len() {
+            violation(info, options, format!("Selection \"{}\" has {} points, but {} weights.", name, selverts.len(), selvertweights.len()))?;
+        }
+        if let Some(i) = selfaces.iter().find(|i| **i >= num_faces) {
+            violation(info, options, format!("Selection \"{}\" references face {}, but there are only {}.", name, i, num_faces))?;
+        }
+        if let Some(s) = selsections.iter().find(|s| **s as usize >= sections.len()) {
+            violation(info, options, format!("Selection \"{}\" references section {}, but there are only {}.", name, s, sections.len()))?;
+        }
+        if let Some(i) = selverts.iter().find(|i| **i >= num_points) {
+            violation(info, options, format!("Selection \"{}\" references point {}, but there are only {}.", name, i, num_points))?;
+        }
+
+        let mut mlod_verts: Vec<u8> = Vec::with_capacity(num_points as usize);
+        let mut mlod_faces: Vec<u8> = Vec::with_capacity(num_faces as usize);
+        mlod_verts.resize(num_points as usize, 0);
+        mlod_faces.resize(num_faces as usize, 0);
+
+        for i in selfaces.into_iter().filter(|i| *i < num_faces) {
+            mlod_faces[i as usize] = 0x1;
+        }
+
+        for s in selsections.into_iter().filter(|s| (*s as usize) < sections.len()) {
+            let section = sections[s as usize];
+            for i in section.0..section.1 {
+                if i >= num_faces {
+                    break;
+                }
+
+                mlod_faces[i as usize] = 0x1;
+                if skipped[i as usize] {
+                    continue;
+                }
+                for j in faces[i as usize].0.iter() {
+                    mlod_verts[*j as usize] = 0x1;
+                }
+            }
+        }
+
+        for (i,w) in selverts.iter().zip(selvertweights.iter()).filter(|(i, _)| **i < num_points) {
+            mlod_verts[*i as usize] = mlod_weight_byte(*w as f32 / 255.0);
+        }
+
+        let mut mlod_faces: Vec<u8> = mlod_faces.into_iter().zip(skipped.iter())
+            .filter(|(_, skip)| !**skip)
+            .map(|(selected, _)| selected)
+            .collect();
+
+        let selected_points: Vec<u32> = (0..num_points).filter(|i| mlod_verts[*i as usize] > 0).collect();
+        info.selections.push(Selection {
+            name: name.clone(),
+            weights: selected_points.iter().map(|i| mlod_weight(mlod_verts[*i as usize])).collect(),
+            points: selected_points,
+            faces: (0..mlod_faces.len() as u32).filter(|i| mlod_faces[*i as usize] > 0).collect(),
+        });
+
+        mlod_verts.append(&mut mlod_faces);
+        lod.taggs.insert(name, mlod_verts.into_boxed_slice());
+    }
+
+    timer.lap("build");
+    Ok(())
+}
+
+/// Reads everything before the LODs.
+pub fn parse_header<I: Read + Seek>(reader: &mut I) -> Result<OdolHeader, CrowbarError> {
+    parse_header_with_options(reader, &ReadOptions::default())
+}
+
+/// Like `parse_header`, with the expected magic and the LOD limit taken from `options`.
+pub fn parse_header_with_options<I: Read + Seek>(reader: &mut I, options: &ReadOptions) -> Result<OdolHeader, CrowbarError> {
+    trace_section(reader, "header")?;
+    let mut buffer = [0; 4];
+    reader.read_exact(&mut buffer)?;
+    if buffer != options.magic {
+        return Err(CrowbarError::Malformed(format!("Missing {} magic.", String::from_utf8_lossy(&options.magic))));
+    }
+
+    let version = reader.read_u32::<LittleEndian>()?;
+    trace!("version: {}", version);
+
+    if !options.versions.is_empty() && !options.versions.contains(&version) {
+        let allowed: Vec<String> = options.versions.iter().map(|v| v.to_string()).collect();
+        return Err(CrowbarError::Unsupported(format!("Version {} is not one of the allowed versions {}.", version, allowed.join(", "))));
+    }
+
+    if version != 71 && version != 73 {
+        return Err(CrowbarError::Unsupported(format!("Version {} is not supported.", version)));
+    }
+
+    let appid = reader.read_u32::<LittleEndian>()?;
+    trace!("appid: {}", appid);
+
+    let game = Game::from_appid(appid);
+    // TODO: DayZ adds fields to the header, which aren't read yet. Reading on as for Arma 3
+    // would desync, so fail before anything is misread.
+    if game == Game::DayZ {
+        return Err(CrowbarError::Unsupported(format!("Models for {} can't be read yet, their extra header fields aren't implemented.", game)));
+    }
+
+    let muzzleflash = reader.read_cstring()?;
+    trace!("muzzleflash: \"{}\"", muzzleflash);
+
+    let num_lods = reader.read_u32::<LittleEndian>()?;
+    trace!("num lods: {}", num_lods);
+    if num_lods > options.max_lods {
+        let position = reader.stream_position()? - 4;
+        return Err(CrowbarError::Malformed(format!("Number of LODs at 0x{:x} is {}, more than the limit of {}.", position, num_lods, options.max_lods)));
+    }
+
+    // Every LOD has a resolution, defaults and an offset in the header.
+    check_remaining(reader, num_lods as u64 * (4 + 3*4 + 4), "LODs")?;
+    let mut resolutions: Vec<Resolution> = Vec::with_capacity(num_lods as usize);
+    for _i in 0..num_lods {
+        let resolution = reader.read_f32::<LittleEndian>()?;
+        trace!("  - {}", resolution);
+        resolutions.push(Resolution(resolution));
+    }
+
+    // Selecting LODs by type or resolution only finds the first of several with the same one.
+    let mut warnings: Vec<String> = Vec::new();
+    for (i, resolution) in resolutions.iter().enumerate() {
+        if resolutions[..i].contains(resolution) {
+            continue;
+        }
+        let indices: Vec<String> = resolutions.iter().enumerate()
+            .filter(|(_, r)| *r == resolution)
+            .map(|(j, _)| j.to_string())
+            .collect();
+        if indices.len() > 1 {
+            header_warning(&mut warnings, options, format!("Resolution {} ({}) is used by LODs {}.", resolution, resolution.lod_type(), indices.join(", ")))?;
+        }
+    }
+
+    let index = reader.read_u32::<LittleEndian>()?;
+    trace!("index: 0x{:x}", index);
+
+    let lod_sphere_memory = reader.read_f32::<LittleEndian>()?;
+    trace!("mem lod sphere: {}", lod_sphere_memory);
+    let lod_sphere_geometry = reader.read_f32::<LittleEndian>()?;
+    trace!("geo lod sphere: {}", lod_sphere_geometry);
+
+    let point_flags = (
+        reader.read_u32::<LittleEndian>()?,
+        reader.read_u32::<LittleEndian>()?,
+        reader.read_u32::<LittleEndian>()?);
+    trace!("point flags: {:x}, {:x}, {:x}", point_flags.0, point_flags.1, point_flags.2);
+
+    let offset_1 = reader.read_vec3()?;
+    trace!("offset 1: {:?}", offset_1);
+
+    let map_icon_color = reader.read_u32::<LittleEndian>()?;
+    trace!("map icon color: {:x}", map_icon_color);
+    let map_selected_color = reader.read_u32::<LittleEndian>()?;
+    trace!("map selected color: {:x}", map_selected_color);
+
+    let view_density = reader.read_f32::<LittleEndian>()?;
+    trace!("view density: {}", view_density);
+
+    let mut bbox_min = reader.read_vec3()?;
+    let mut bbox_max = reader.read_vec3()?;
+    trace!("bounding box: {:?} - {:?}", bbox_min, bbox_max);
+
+    let lod_density_coef = reader.read_f32::<LittleEndian>()?;
+    trace!("lod density coef: {:?}", lod_density_coef);
+    let draw_importance = reader.read_f32::<LittleEndian>()?;
+    trace!("draw importance: {:?}", draw_importance);
+
+    let mut bbox_visual_min = reader.read_vec3()?;
+    let mut bbox_visual_max = reader.read_vec3()?;
+    trace!("bounding box visual: {:?} - {:?}", bbox_visual_min, bbox_visual_max);
+
+    let mut bounding_center = reader.read_vec3()?;
+    trace!("bounding center: {:?}", bounding_center);
+
+    let mut geometry_center = reader.read_vec3()?;
+    trace!("geometry center: {:?}", geometry_center);
+
+    let mut cog_offset = reader.read_vec3()?;
+    trace!("cog offset: {:?}", cog_offset);
+
+    // A broken bounding center would carry over into every point.
+    let mut non_finite = 0;
+    for value in [&mut bbox_min, &mut bbox_max, &mut bbox_visual_min, &mut bbox_visual_max, &mut bounding_center, &mut geometry_center, &mut cog_offset].iter_mut() {
+        sanitize_vec3(value, options, &mut non_finite);
+    }
+    if non_finite > 0 {
+        header_warning(&mut warnings, options, non_finite_message(non_finite, "the bounding data", options))?;
+    }
+
+    let inv_inertia = [reader.read_vec3()?, reader.read_vec3()?, reader.read_vec3()?];
+    trace!("inv inertia: {:?}", inv_inertia[0]);
+    trace!("             {:?}", inv_inertia[1]);
+    trace!("             {:?}", inv_inertia[2]);
+
+    let autocenter = reader.read_u8()?;
+    trace!("autocenter: 0x{:x}", autocenter);
+    let lock_autocenter = reader.read_u8()?;
+    trace!("lock autocenter: 0x{:x}", lock_autocenter);
+    let can_occlude = reader.read_u8()?;
+    trace!("can occlude: 0x{:x}", can_occlude);
+    let can_be_occluded = reader.read_u8()?;
+    trace!("can be occluded: 0x{:x}", can_be_occluded);
+    let ai_cover = reader.read_u8()?;
+    trace!("ai cover: 0x{:x}", ai_cover);
+
+    let skeleton_ht_min = reader.read_f32::<LittleEndian>()?;
+    trace!("skeleton ht min: {:?}", skeleton_ht_min);
+    let skeleton_ht_max = reader.read_f32::<LittleEndian>()?;
+    trace!("skeleton ht max: {:?}", skeleton_ht_max);
+    let skeleton_af_max = reader.read_f32::<LittleEndian>()?;
+    trace!("skeleton af max: {:?}", skeleton_af_max);
+    let skeleton_mf_max = reader.read_f32::<LittleEndian>()?;
+    trace!("skeleton mf max: {:?}", skeleton_mf_max);
+    let skeleton_mf_act = reader.read_f32::<LittleEndian>()?;
+    trace!("skeleton mf act: {:?}", skeleton_mf_act);
+    let skeleton_t_body = reader.read_f32::<LittleEndian>()?;
+    trace!("skeleton t body: {:?}", skeleton_t_body);
+
+    let force_not_alpha = reader.read_u8()?;
+    trace!("force not alpha: 0x{:x}", force_not_alpha);
+    let sb_source = reader.read_i32::<LittleEndian>()?;
+    trace!("sb source: {}", sb_source);
+    let prefer_shadow_volume = reader.read_u8()?;
+    trace!("prefer shadow volume: 0x{:x}", prefer_shadow_volume);
+    let shadow_offset = reader.read_f32::<LittleEndian>()?;
+    trace!("shadow offset: {}", shadow_offset);
+    let animated = reader.read_u8()?;
+    trace!("animated: 0x{:x}", animated);
+
+    trace_section(reader, "skeleton")?;
+    let skeleton_name = reader.read_cstring()?;
+    trace!("skeleton name: \"{}\"", skeleton_name);
+
+    let mut bones: Vec<(String, String)> = Vec::new();
+    if skeleton_name != "" {
+        reader.seek(SeekFrom::Current(1))?;
+        let num_bones = reader.read_u32::<LittleEndian>()?;
+        trace!("num bones: {}", num_bones);
+        check_remaining(reader, num_bones as u64 * 2, "Bones")?;
+        for _i in 0..num_bones {
+            let name = reader.read_cstring()?;
+            let parent = reader.read_cstring()?;
+            trace!("  - {} -> {}", name, parent);
+            bones.push((name, parent));
+        }
+
+        // The bone list is followed by a string that is empty in every known model. It is
+        // skipped as a whole, so a non-empty one doesn't throw off the rest of the header.
+        let trailing = reader.read_cstring()?;
+        if !trailing.is_empty() {
+            header_warning(&mut warnings, options, format!("Unexpected data after bones of skeleton \"{}\": \"{}\"", skeleton_name, trailing))?;
+        }
+    }
+
+    let map_type = reader.read_u8()?;
+    trace!("map type: 0x{:x}", map_type);
+
+    let num_floats = reader.read_u32::<LittleEndian>()?;
+    trace!("num floats: {}", num_floats);
+    reader.seek(SeekFrom::Current(num_floats as i64 * 4))?;
+
+    let mass = reader.read_f32::<LittleEndian>()?;
+    trace!("mass: {:?}", mass);
+    let mass_inv = reader.read_f32::<LittleEndian>()?;
+    trace!("mass inv: {:?}", mass_inv);
+    let armor = reader.read_f32::<LittleEndian>()?;
+    trace!("armor: {:?}", armor);
+    let armor_inv = reader.read_f32::<LittleEndian>()?;
+    trace!("armor inv: {:?}", armor_inv);
+
+    let mut special_lods = [0; 14];
+    trace!("lod indices:");
+    for (i, name) in SPECIAL_LOD_NAMES.iter().enumerate() {
+        special_lods[i] = reader.read_i8()?;
+        trace!("  {}: {}", name, special_lods[i]);
+    }
+
+    reader.seek(SeekFrom::Current(4))?;
+
+    trace_section(reader, "shadow")?;
+    let min_shadow = reader.read_u32::<LittleEndian>()?;
+    trace!("min shadow: {}", min_shadow);
+    let can_blend = reader.read_u8()?;
+    trace!("can blend: 0x{:x}", can_blend);
+
+    trace_section(reader, "class types")?;
+    let class_type = reader.read_cstring()?;
+    trace!("class type: \"{}\"", class_type);
+    let destruct_type = reader.read_cstring()?;
+    trace!("destruct type: \"{}\"", destruct_type);
+
+    reader.seek(SeekFrom::Current((if version >= 73 { 1 } else { 0 } + 4) as i64))?;
+
+    trace!("lod defaults:");
+    let mut lod_defaults: Vec<(u32, u32, u32)> = Vec::with_capacity(num_lods as usize);
+    for _i in 0..num_lods {
+        let defaults = (
+            reader.read_u32::<LittleEndian>()?,
+            reader.read_u32::<LittleEndian>()?,
+            reader.read_u32::<LittleEndian>()?);
+        trace!("  - {:x} {:x} {:x}", defaults.0, defaults.1, defaults.2);
+        lod_defaults.push(defaults);
+    }
+    trace_section(reader, "animations")?;
+    let animations = read_animations(reader, &bones, options)?;
+
+    trace_section(reader, "lod offsets")?;
+    let mut lod_indices: Vec<u32> = Vec::with_capacity(num_lods as usize);
+    for _i in 0..num_lods {
+        lod_indices.push(reader.read_u32::<LittleEndian>()?);
+    }
+
+    trace!("lod indices: {:?}", lod_indices);
+
+    Ok(OdolHeader {
+        version,
+        appid,
+        game,
+        muzzleflash,
+        resolutions,
+        lod_offsets: lod_indices,
+        index,
+        lod_sphere_memory,
+        lod_sphere_geometry,
+        point_flags,
+        offset_1,
+        map_icon_color,
+        map_selected_color,
+        view_density,
+        bbox_min,
+        bbox_max,
+        bbox_visual_min,
+        bbox_visual_max,
+        bounding_center,
+        geometry_center,
+        cog_offset,
+        lod_density_coef,
+        draw_importance,
+        shadow_offset,
+        inv_inertia,
+        autocenter: autocenter != 0,
+        lock_autocenter: lock_autocenter != 0,
+        can_occlude: can_occlude != 0,
+        can_be_occluded: can_be_occluded != 0,
+        ai_cover: ai_cover != 0,
+        force_not_alpha: force_not_alpha != 0,
+        prefer_shadow_volume: prefer_shadow_volume != 0,
+        animated: animated != 0,
+        skeleton_ht_min,
+        skeleton_ht_max,
+        skeleton_af_max,
+        skeleton_mf_max,
+        skeleton_mf_act,
+        skeleton_t_body,
+        sb_source,
+        skeleton_name,
+        bones,
+        map_type,
+        mass,
+        mass_inv,
+        armor,
+        armor_inv,
+        special_lods,
+        min_shadow,
+        can_blend: can_blend != 0,
+        class_type,
+        destruct_type,
+        lod_defaults,
+        animations,
+        warnings,
+    })
+}
+
+fn empty_lod(resolution: f32) -> LOD {
+    LOD {
+        version_major: 28,
+        version_minor: 256,
+        resolution,
+        points: Vec::new(),
+        face_normals: Vec::new(),
+        faces: Vec::new(),
+        taggs: LinkedHashMap::new(),
+    }
+}
+
+/// Reads a single LOD, given the header that was parsed from the same reader. Unlike
+/// `read_odol_from_reader`, this doesn't add the header's named properties to the geometry LOD.
+pub fn read_lod_at<I: Read + Seek>(reader: &mut I, header: &OdolHeader, index: usize, options: &ReadOptions) -> Result<(LOD, LodInfo), CrowbarError> {
+    let (resolution, offset) = match (header.resolutions.get(index), header.lod_offsets.get(index)) {
+        (Some(resolution), Some(offset)) => (resolution.0, *offset),
+        _ => return Err(CrowbarError::Io(Error::new(ErrorKind::InvalidInput, format!("The model has no LOD {}.", index)))),
+    };
+    let center = if options.skip_bounding_offset { (0.0, 0.0, 0.0) } else { header.bounding_center };
+
+    let deadline = options.timeout.map(|timeout| Instant::now() + timeout);
+
+    let mut lod = empty_lod(resolution);
+    let mut info = LodInfo::default();
+    reader.seek(SeekFrom::Start(offset as u64))?;
+    if let Err(e) = read_lod(reader, &mut lod, &mut info, center, options, deadline) {
+        if options.debug_offsets {
+            dump_offset(reader);
+        }
+        return Err(e);
+    }
+
+    Ok((lod, info))
+}
+
+/// Parses the header and calls `f` with the reader at the start of every LOD, with the
+/// skipped bytes, magic, LOD limit, versions and timeout of `options` applied like when
+/// reading whole models.
+fn visit_lod_starts<I, F>(reader: &mut I, options: &ReadOptions, mut f: F) -> Result<(), CrowbarError>
+where
+    I: Read + Seek,
+    F: FnMut(&mut dyn ReadSeek) -> Result<(), CrowbarError>,
+{
+    let mut offset_reader;
+    let mut reader: &mut dyn ReadSeek = if options.skip_bytes > 0 {
+        offset_reader = Offset::new(reader, options.skip_bytes)?;
+        &mut offset_reader
+    } else {
+        reader
+    };
+
+    let deadline = options.timeout.map(|timeout| Instant::now() + timeout);
+    let header = parse_header_with_options(&mut reader, options)?;
+    for offset in header.lod_offsets.iter() {
+        check_deadline(deadline, *offset as u64)?;
+        reader.seek(SeekFrom::Start(*offset as u64))?;
+        f(reader)?;
+    }
+
+    Ok(())
+}
+
+/// Returns the sorted texture and material paths used by any LOD, without reading geometry.
+/// Procedural textures are left out.
+pub fn read_paths<I: Read + Seek>(reader: &mut I, options: &ReadOptions) -> Result<(Vec<String>, Vec<String>), CrowbarError> {
+    let mut textures: BTreeSet<String> = BTreeSet::new();
+    let mut materials: BTreeSet<String> = BTreeSet::new();
+    visit_lod_starts(reader, options, |mut reader| {
+        let (_, lod_textures, lod_materials) = read_lod_paths(&mut reader)?;
+        textures.extend(lod_textures.into_iter().filter(|t| !t.is_empty() && !t.starts_with('#')));
+        materials.extend(lod_materials.into_iter().map(|m| m.path).filter(|m| !m.is_empty()));
+        Ok(())
+    })?;
+
+    Ok((textures.into_iter().collect(), materials.into_iter().collect()))
+}
+
+/// Returns the number of faces of every LOD. Only the start of each LOD is read, nothing is
+/// decompressed.
+pub fn count_faces<I: Read + Seek>(reader: &mut I, options: &ReadOptions) -> Result<Vec<u32>, CrowbarError> {
+    let mut counts: Vec<u32> = Vec::new();
+    visit_lod_starts(reader, options, |mut reader| {
+        read_lod_paths(&mut reader)?;
+        counts.push(read_num_faces(&mut reader)?);
+        Ok(())
+    })?;
+
+    Ok(counts)
+}
+
+/// Prints the reader position and a hex dump of the 64 bytes around it to stderr. This is
+/// only a debugging aid, so failing to read the bytes is not an error.
+fn dump_offset<I: Read + Seek>(reader: &mut I) {
+    let offset = match reader.stream_position() {
+        Ok(offset) => offset,
+        Err(_) => return,
+    };
+    let start = offset.saturating_sub(32) & !0xf;
+
+    let mut bytes: Vec<u8> = Vec::with_capacity(64);
+    if reader.seek(SeekFrom::Start(start)).is_err() || reader.take(64).read_to_end(&mut bytes).is_err() {
+        return;
+    }
+    let _ = reader.seek(SeekFrom::Start(offset));
+
+    eprintln!("Parse failed at 0x{:x}:", offset);
+    for (i, line) in bytes.chunks(16).enumerate() {
+        let line_start = start + i as u64 * 16;
+        let hex: Vec<String> = line.iter().enumerate().map(|(j, b)| {
+            let marker = if line_start + j as u64 == offset { '>' } else { ' ' };
+            format!("{}{:02x}", marker, b)
+        }).collect();
+        let ascii: String = line.iter().map(|b| if b.is_ascii_graphic() || *b == b' ' { *b as char } else { '.' }).collect();
+        eprintln!("  {:08x} {:<48}  |{}|", line_start, hex.join(""), ascii);
+    }
+}
+
+/// Receives the parts of a model while it is read by `parse_with`. Every method does nothing
+/// by default, so a visitor only implements what it needs. A LOD is read completely before
+/// its parts are passed on, so at most one LOD is in memory at once.
+pub trait OdolVisitor {
+    fn on_header(&mut self, _header: &OdolHeader) {}
+
+    /// Called before a LOD is read, with its index in the file.
+    fn on_lod_header(&mut self, _index: usize, _resolution: f32) {}
+
+    fn on_material(&mut self, _index: usize, _material: &Material) {}
+
+    fn on_selection(&mut self, _index: usize, _selection: &Selection) {}
+
+    fn on_points(&mut self, _index: usize, _points: &[Point]) {}
+
+    /// Called last for every LOD, which is dropped afterwards unless the visitor keeps it.
+    fn on_lod(&mut self, _index: usize, _lod: LOD, _info: LodInfo) -> Result<(), CrowbarError> {
+        Ok(())
+    }
+}
+
+/// Collects all LODs into a `ParsedModel`.
+struct ModelCollector {
+    lods: Vec<LOD>,
+    infos: Vec<LodInfo>,
+}
+
+impl OdolVisitor for ModelCollector {
+    fn on_lod(&mut self, _index: usize, lod: LOD, info: LodInfo) -> Result<(), CrowbarError> {
+        self.lods.push(lod);
+        self.infos.push(info);
+        Ok(())
+    }
+}
+
+/// Passes every LOD to a closure, for `read_odol_lods`.
+struct LodCallback<F>(F);
+
+impl<F: FnMut(LOD, LodInfo) -> Result<(), CrowbarError>> OdolVisitor for LodCallback<F> {
+    fn on_lod(&mut self, _index: usize, lod: LOD, info: LodInfo) -> Result<(), CrowbarError> {
+        (self.0)(lod, info)
+    }
+}
+
+/// Fails if the MLOD version in the options isn't one that can be written.
+pub(crate) fn check_mlod_version(options: &ReadOptions) -> Result<(), CrowbarError> {
+    if MLOD_VERSIONS.contains(&options.mlod_version) {
+        Ok(())
+    } else {
+        Err(CrowbarError::Unsupported(format!("MLOD version {} can't be written.", options.mlod_version)))
+    }
+}
+
+pub fn read_odol_from_reader<I: Read + Seek>(reader: &mut I, options: &ReadOptions) -> Result<ParsedModel, CrowbarError> {
+    check_mlod_version(options)?;
+
+    let mut collector = ModelCollector { lods: Vec::new(), infos: Vec::new() };
+    let header = parse_with(reader, options, &mut collector)?;
+
+    Ok(ParsedModel {
+        header,
+        mlod: P3D {
+            version: options.mlod_version,
+            lods: collector.lods
+        },
+        lods: collector.infos,
+    })
+}
+
+/// Reads a model from data that is already in memory, without copying it. Takes anything that
+/// can be viewed as bytes, like a `Vec<u8>`, a slice or a `bytes::Bytes`.
+pub fn read_odol_from_bytes<B: AsRef<[u8]>>(data: B, options: &ReadOptions) -> Result<ParsedModel, CrowbarError> {
+    read_odol_from_reader(&mut Cursor::new(data), options)
+}
+
+/// Like `read_odol_from_reader`, for readers that can't seek. The whole stream is read into
+/// memory first, so this needs as much memory as the file is large on top of the model.
+pub fn read_odol_streaming<R: Read>(reader: &mut R, options: &ReadOptions) -> Result<ParsedModel, CrowbarError> {
+    let mut data: Vec<u8> = Vec::new();
+    reader.read_to_end(&mut data)?;
+    read_odol_from_reader(&mut Cursor::new(data), options)
+}
+
+/// Adds the named properties that come from the header to a geometry LOD. The class and
+/// destruct type and most header flags come from named properties of the geometry LOD, which
+/// usually still has them anyway. Lock autocenter and animated are derived by binarize and have
+/// no property, the shadow source and min shadow are only kept in the header. The default map
+/// type hide is left out, like Object Builder does.
+fn add_header_properties(header: &OdolHeader, lod: &LOD, info: &mut LodInfo) {
+    if LodType::from_resolution(lod.resolution) != LodType::Geometry {
+        return;
+    }
+
+    let flag = |value: bool| (if value { "1" } else { "0" }).to_string();
+    let properties = [
+        ("class", header.class_type.clone()),
+        ("damage", header.destruct_type.clone()),
+        ("autocenter", flag(header.autocenter)),
+        ("canocclude", flag(header.can_occlude)),
+        ("canbeoccluded", flag(header.can_be_occluded)),
+        ("aicovers", flag(header.ai_cover)),
+        ("forcenotalpha", flag(header.force_not_alpha)),
+        ("prefershadowvolume", flag(header.prefer_shadow_volume)),
+        ("canblend", flag(header.can_blend)),
+        ("map", header.map_type_name().filter(|m| *m != "hide").unwrap_or("").to_string()),
+    ];
+
+    for (name, value) in properties.iter() {
+        if !value.is_empty() && !info.properties.iter().any(|(n, _)| n.eq_ignore_ascii_case(name)) {
+            info.properties.push((name.to_string(), value.to_string()));
+        }
+    }
+}
+
+/// Reads the header, then every LOD in turn and passes its parts to `visitor`.
+fn visit_lods<I: Read + Seek, V: OdolVisitor>(reader: &mut I, options: &ReadOptions, visitor: &mut V) -> Result<OdolHeader, CrowbarError> {
+    let deadline = options.timeout.map(|timeout| Instant::now() + timeout);
+    let mut timer = Timer::start(|| "model".to_string());
+    let header = parse_header_with_options(reader, options)?;
+    timer.lap("header");
+    visitor.on_header(&header);
+    let center = if options.skip_bounding_offset { (0.0, 0.0, 0.0) } else { header.bounding_center };
+
+    let first_lod = if options.first_lod_only {
+        let first_lod = header.resolutions.iter()
+            .enumerate()
+            .filter(|(_, r)| r.is_visual())
+            .min_by(|(_, a), (_, b)| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(i, _)| i);
+        if first_lod.is_none() {
+            return Err(CrowbarError::Io(Error::new(ErrorKind::InvalidInput, "The model has no visual LOD.")));
+        }
+        first_lod
+    } else {
+        None
+    };
+
+    for (i, (resolution, offset)) in header.resolutions.iter().zip(header.lod_offsets.iter()).enumerate() {
+        if first_lod.is_some() && first_lod != Some(i) {
+            continue;
+        }
+
+        check_deadline(deadline, *offset as u64)?;
+        visitor.on_lod_header(i, resolution.0);
+        let mut lod = empty_lod(resolution.0);
+
+        reader.seek(SeekFrom::Start(*offset as u64))?;
+        trace_section(reader, &format!("LOD {}", LodType::from_resolution(lod.resolution)))?;
+
+        let mut info = LodInfo::default();
+        if let Err(e) = read_lod(reader, &mut lod, &mut info, center, options, deadline) {
+            if options.strict || matches!(e, CrowbarError::Timeout(_)) || (!options.keep_going && !options.validate && !options.repair) {
+                return Err(e);
+            }
+
+            if options.debug_offsets {
+                dump_offset(reader);
+            }
+
+            info.diagnostics.push(format!("Failed to read the LOD, kept what was read before: {}", e));
+        }
+
+        add_header_properties(&header, &lod, &mut info);
+        for material in info.materials.iter() {
+            visitor.on_material(i, material);
+        }
+        for selection in info.selections.iter() {
+            visitor.on_selection(i, selection);
+        }
+        visitor.on_points(i, &lod.points);
+        visitor.on_lod(i, lod, info)?;
+    }
+
+    Ok(header)
+}
+
+/// Reads a model and passes its parts to `visitor` as they are read, instead of returning it.
+/// Returns the header.
+pub fn parse_with<I: Read + Seek, V: OdolVisitor>(reader: &mut I, options: &ReadOptions, visitor: &mut V) -> Result<OdolHeader, CrowbarError> {
+    if options.skip_bytes > 0 {
+        let mut reader = Offset::new(reader, options.skip_bytes)?;
+        let result = visit_lods(&mut reader, options, visitor);
+        if result.is_err() && options.debug_offsets {
+            dump_offset(&mut reader);
+        }
+        return result;
+    }
+
+    let result = visit_lods(reader, options, visitor);
+    if result.is_err() && options.debug_offsets {
+        dump_offset(reader);
+    }
+    result
+}
+
+/// Like `read_odol_from_reader`, but instead of returning the model, passes every LOD to `f`
+/// right after it was read. The LOD is dropped afterwards, so memory use is bounded by the
+/// largest LOD instead of the whole model. Returns the header.
+pub fn read_odol_lods<I, F>(reader: &mut I, options: &ReadOptions, f: F) -> Result<OdolHeader, CrowbarError>
+where
+    I: Read + Seek,
+    F: FnMut(LOD, LodInfo) -> Result<(), CrowbarError>,
+{
+    parse_with(reader, options, &mut LodCallback(f))
+}
+
+#[cfg(not(feature = "wasm"))]
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+#[cfg(not(feature = "wasm"))]
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Opens a file for reading. Files wrapped in gzip are decompressed into memory first, all
+/// others are read as they are.
+#[cfg(not(feature = "wasm"))]
+pub fn open_input(path: &Path) -> Result<Box<dyn ReadSeek>, CrowbarError> {
+    unwrap_compressed(BufReader::new(File::open(path)?))
+}
+
+#[cfg(not(feature = "wasm"))]
+fn unwrap_compressed<R: BufRead + Seek + 'static>(mut reader: R) -> Result<Box<dyn ReadSeek>, CrowbarError> {
+    let magic = reader.fill_buf()?;
+    if magic.starts_with(&ZSTD_MAGIC) {
+        return Err(CrowbarError::Unsupported("Zstandard-compressed files can't be read, decompress it first.".to_string()));
+    }
+    if !magic.starts_with(&GZIP_MAGIC) {
+        return Ok(Box::new(reader));
+    }
+
+    // Only the first member of a multi-member file is read.
+    let mut data: Vec<u8> = Vec::new();
+    GzDecoder::new(reader).read_to_end(&mut data).map_err(|e| match e.kind() {
+        ErrorKind::InvalidInput | ErrorKind::InvalidData | ErrorKind::UnexpectedEof => {
+            CrowbarError::Malformed(format!("Invalid gzip data: {}", e))
+        },
+        _ => CrowbarError::Io(e),
+    })?;
+    Ok(Box::new(Cursor::new(data)))
+}
+
+#[cfg(not(feature = "wasm"))]
+pub fn read_odol(path: PathBuf, options: &ReadOptions) -> Result<ParsedModel, CrowbarError> {
+    let mut reader = open_input(&path)?;
+    read_odol_from_reader(&mut reader, options)
+}
+
+/// Returns the resolution and the start and end offset of every LOD without parsing them. A
+/// LOD is assumed to end where the next one in the file starts, the last one at the end of file.
+pub fn lod_byte_ranges<I: Read + Seek>(reader: &mut I) -> Result<Vec<(f32, u64, u64)>, Error> {
+    let header = parse_header(reader)?;
+    let eof = reader.seek(SeekFrom::End(0))?;
+
+    Ok(header.resolutions.iter().zip(header.lod_offsets.iter()).map(|(resolution, start)| {
+        let end = header.lod_offsets.iter()
+            .filter(|o| *o > start)
+            .min()
+            .map(|o| *o as u64)
+            .unwrap_or(eof);
+        (resolution.0, *start as u64, end)
+    }).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::fixture;
+
+    fn read(model: &fixture::Model) -> ParsedModel {
+        read_odol_from_bytes(model.to_bytes(), &ReadOptions::default()).unwrap()
+    }
+
+    /// A row of points, enough for faces of any shape.
+    fn points(n: usize) -> Vec<(f32, f32, f32)> {
+        (0..n).map(|i| (i as f32, (i % 2) as f32, 0.0)).collect()
+    }
+
+    #[test]
+    fn sections_of_triangles_and_quads() {
+        let mut lod = fixture::Lod::new(1.0);
+        lod.points = points(8);
+        lod.faces = vec![
+            vec![0, 1, 2],
+            vec![0, 1, 2, 3],
+            vec![1, 2, 3, 4],
+            vec![2, 3, 4],
+            vec![3, 4, 5, 6],
+            vec![4, 5, 6],
+            vec![5, 6, 7],
+        ];
+        lod.textures = vec!["a\\a_co.paa".to_string(), "a\\b_co.paa".to_string(), "a\\c_co.paa".to_string()];
+        lod.materials = vec!["a\\a.rvmat".to_string(), "a\\b.rvmat".to_string()];
+        lod.sections = vec![
+            fixture::Section { faces: 0..2, texture: 0, material: 0 },
+            fixture::Section { faces: 2..3, texture: 1, material: -1 },
+            fixture::Section { faces: 3..6, texture: 2, material: 1 },
+            fixture::Section { faces: 6..7, texture: 0, material: 1 },
+        ];
+        lod.selections.push(fixture::Selection { name: "third".to_string(), sections: vec![2], ..Default::default() });
+
+        let model = read(&fixture::Model::new(vec![lod]));
+        let lod = &model.mlod.lods[0];
+
+        let faces: Vec<(usize, &str, &str)> = lod.faces.iter().map(|f| (f.vertices.len(), f.texture.as_str(), f.material.as_str())).collect();
+        assert_eq!(faces, [
+            (3, "a\\a_co.paa", "a\\a.rvmat"),
+            (4, "a\\a_co.paa", "a\\a.rvmat"),
+            (4, "a\\b_co.paa", ""),
+            (3, "a\\c_co.paa", "a\\b.rvmat"),
+            (4, "a\\c_co.paa", "a\\b.rvmat"),
+            (3, "a\\c_co.paa", "a\\b.rvmat"),
+            (3, "a\\a_co.paa", "a\\b.rvmat"),
+        ]);
+
+        let selection = &model.lods[0].selections[0];
+        assert_eq!(selection.faces, [3, 4, 5]);
+        assert_eq!(selection.points, [2, 3, 4, 5, 6]);
+        assert!(model.lods[0].diagnostics.is_empty());
+    }
+
+    #[test]
+    fn animations_as_binarize_writes_them() {
+        // Written out by hand following the layout binarize uses, independent of the fixture.
+        let data: &[u8] = &[
+            1, // has animations
+            3, 0, 0, 0, // number of animations
+
+            // rotation "door" of source "door", by 0 to 1.5
+            0, 0, 0, 0,
+            b'd', b'o', b'o', b'r', 0,
+            b'd', b'o', b'o', b'r', 0,
+            0, 0, 0, 0, 0, 0, 0x80, 0x3f, // min and max value
+            0, 0, 0, 0, 0, 0, 0x80, 0x3f, // min and max phase
+            0x17, 0xb7, 0xd1, 0x38, 0, 0, 0, 0,
+            0, 0, 0, 0, // clamp
+            0, 0, 0, 0, 0, 0, 0xc0, 0x3f, // angles
+
+            // direct "dial" of source "time"
+            8, 0, 0, 0,
+            b'd', b'i', b'a', b'l', 0,
+            b't', b'i', b'm', b'e', 0,
+            0, 0, 0, 0, 0, 0, 0x80, 0x3f,
+            0, 0, 0, 0, 0, 0, 0x80, 0x3f,
+            0x17, 0xb7, 0xd1, 0x38, 0, 0, 0, 0,
+            2, 0, 0, 0, // loop
+            0, 0, 0, 0, 0, 0, 0x80, 0x3f, 0, 0, 0, 0, // axis position (0, 1, 0)
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x80, 0x3f, // axis direction (0, 0, 1)
+            0, 0, 0, 0x40, // angle 2
+            0, 0, 0, 0x3f, // axis offset 0.5
+
+            // hide "lamp" of source "damage", hidden from 0.5 on
+            9, 0, 0, 0,
+            b'l', b'a', b'm', b'p', 0,
+            b'd', b'a', b'm', b'a', b'g', b'e', 0,
+            0, 0, 0, 0, 0, 0, 0x80, 0x3f,
+            0, 0, 0, 0, 0, 0, 0x80, 0x3f,
+            0x17, 0xb7, 0xd1, 0x38, 0, 0, 0, 0,
+            0, 0, 0, 0,
+            0, 0, 0, 0x3f, 0, 0, 0x80, 0x3f, // hide and unhide value
+
+            2, 0, 0, 0, // number of resolutions
+            // the animations of both bones, per resolution
+            2, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0,
+            2, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0,
+            // the bone of every animation per resolution, with the axis of the rotation
+            0, 0, 0, 0,
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, // axis position (0, 0, 0)
+            0, 0, 0x80, 0x3f, 0, 0, 0, 0, 0, 0, 0, 0, // axis direction (1, 0, 0)
+            1, 0, 0, 0,
+            0, 0, 0, 0,
+            // the door isn't animated in the second resolution
+            0xff, 0xff, 0xff, 0xff,
+            1, 0, 0, 0,
+            0, 0, 0, 0,
+        ];
+        let bones = vec![("door".to_string(), "".to_string()), ("dial".to_string(), "".to_string())];
+
+        let mut reader = Cursor::new(data);
+        let animations = read_animations(&mut reader, &bones, &ReadOptions { strict: true, ..Default::default() }).unwrap();
+        assert_eq!(reader.position(), data.len() as u64);
+
+        let read: Vec<_> = animations.iter()
+            .map(|a| (a.name.as_str(), a.anim_type, a.source.as_str(), a.source_address, a.params.as_slice(), a.bone.as_deref()))
+            .collect();
+        assert_eq!(read, [
+            ("door", 0, "door", 0, &[0.0, 1.5][..], Some("door")),
+            ("dial", 8, "time", 2, &[0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 2.0, 0.5][..], Some("dial")),
+            ("lamp", 9, "damage", 0, &[0.5, 1.0][..], Some("door")),
+        ]);
+    }
+
+    #[test]
+    fn every_number_of_uvs_and_uv_sets() {
+        let uvs = vec![(0.0, 0.5), (1.0, 0.0), (0.5, 1.0)];
+        let second = vec![(1.0, 1.0), (0.0, 0.0), (0.5, 0.5)];
+        let cases = vec![
+            (vec![], Some(0)),
+            (vec![vec![]], None),
+            (vec![uvs.clone()], Some(0)),
+            (vec![uvs.clone()], None),
+            (vec![vec![], second.clone()], None),
+            (vec![uvs.clone(), second.clone()], None),
+            (vec![uvs.clone(), vec![], second.clone()], None),
+        ];
+
+        for (uv_sets, num_uv_sets) in cases {
+            let description = format!("{} UVs, {:?} sets", uv_sets.first().map(|s| s.len()).unwrap_or(0), num_uv_sets.unwrap_or(uv_sets.len() as u32));
+            let mut lod = fixture::Lod::new(1.0);
+            lod.points = points(3);
+            lod.faces = vec![vec![0, 1, 2]];
+            lod.uv_sets = uv_sets.clone();
+            lod.num_uv_sets = num_uv_sets;
+            // The points come after the UV sets, so they are only right if those were read in full.
+            let model = read(&fixture::Model::new(vec![lod, fixture::Lod::new(2.0)]));
+            assert_eq!(model.mlod.lods.len(), 2, "{}", description);
+            let (lod, info) = (&model.mlod.lods[0], &model.lods[0]);
+            assert_eq!(lod.points.iter().map(|p| p.coords).collect::<Vec<_>>(), points(3), "{}", description);
+
+            let mut face_uvs = vec![(0.0, 0.0); 3];
+            for vertex in lod.faces[0].vertices.iter() {
+                face_uvs[vertex.point_index as usize] = vertex.uv;
+            }
+            match uv_sets.first() {
+                Some(first) if !first.is_empty() => assert_eq!(face_uvs, *first, "{}", description),
+                _ => assert_eq!(face_uvs, [(0.0, 0.0); 3], "{}", description),
+            }
+
+            // Empty sets get a UV for every point, like the first one.
+            let extra_sets: Vec<Vec<(f32, f32)>> = uv_sets.iter()
+                .skip(1)
+                .take(num_uv_sets.unwrap_or(u32::MAX) as usize)
+                .map(|set| if set.is_empty() { vec![(0.0, 0.0); 3] } else { set.clone() })
+                .collect();
+            assert_eq!(info.uv_sets, extra_sets, "{}", description);
+
+            let uvs_without_sets = num_uv_sets == Some(0) && uv_sets.first().map(|s| !s.is_empty()).unwrap_or(false);
+            assert_eq!(info.diagnostics.len(), uvs_without_sets as usize, "{}: {:?}", description, info.diagnostics);
+        }
+    }
+
+    #[test]
+    fn offsets_after_animations() {
+        let mut lod = fixture::Lod::new(1.0);
+        lod.points = points(4);
+        lod.faces = vec![vec![0, 1, 2, 3]];
+        lod.sections.push(fixture::Section { faces: 0..1, texture: 0, material: -1 });
+        let mut memory = fixture::Lod::new(1e15);
+        memory.points = points(3);
+
+        let mut model = fixture::Model::new(vec![lod, memory]);
+        model.skeleton_name = "skeleton".to_string();
+        model.bones = vec![
+            ("door".to_string(), "".to_string()),
+            ("handle".to_string(), "door".to_string()),
+            ("light".to_string(), "".to_string()),
+        ];
+        // Only rotations and translations attached to a bone store an axis per resolution.
+        model.animations = vec![
+            fixture::Animation::new(0, "door_rot", "door", Some(0)),
+            fixture::Animation::new(4, "handle_move", "handle", Some(1)),
+            fixture::Animation::new(8, "handle_direct", "handle", Some(1)),
+            fixture::Animation::new(9, "light_hide", "damage", Some(2)),
+            fixture::Animation::new(1, "unused", "time", None),
+        ];
+        let data = model.to_bytes();
+
+        let mut reader = Cursor::new(&data);
+        let header = parse_header_with_options(&mut reader, &ReadOptions { strict: true, ..Default::default() }).unwrap();
+        assert_eq!(reader.position(), header.lod_offsets[0] as u64);
+
+        let animations: Vec<(&str, u32, usize, Option<&str>)> = header.animations.iter()
+            .map(|a| (a.name.as_str(), a.anim_type, a.params.len(), a.bone.as_deref()))
+            .collect();
+        assert_eq!(animations, [
+            ("door_rot", 0, 2, Some("door")),
+            ("handle_move", 4, 2, Some("handle")),
+            ("handle_direct", 8, 8, Some("handle")),
+            ("light_hide", 9, 2, Some("light")),
+            ("unused", 1, 2, None),
+        ]);
+
+        let model = read_odol_from_bytes(&data, &ReadOptions { strict: true, ..Default::default() }).unwrap();
+        let num_points: Vec<usize> = model.mlod.lods.iter().map(|l| l.points.len()).collect();
+        assert_eq!(num_points, [4, 3]);
+    }
+
+    #[test]
+    fn paths_and_counts_follow_options() {
+        let mut lod = fixture::Lod::new(1.0);
+        lod.points = points(4);
+        lod.faces = vec![vec![0, 1, 2], vec![0, 1, 2, 3]];
+        lod.textures = vec!["a\\a_co.paa".to_string()];
+        lod.materials = vec!["a\\a.rvmat".to_string()];
+        lod.sections.push(fixture::Section { faces: 0..2, texture: 0, material: 0 });
+        let mut data = vec![0xff; 16];
+        data.extend(fixture::Model::new(vec![lod, fixture::Lod::new(1e15)]).to_bytes());
+
+        let options = ReadOptions { skip_bytes: 16, ..Default::default() };
+        assert_eq!(count_faces(&mut Cursor::new(&data), &options).unwrap(), [2, 0]);
+        let (textures, materials) = read_paths(&mut Cursor::new(&data), &options).unwrap();
+        assert_eq!(textures, ["a\\a_co.paa"]);
+        assert_eq!(materials, ["a\\a.rvmat"]);
+
+        assert!(count_faces(&mut Cursor::new(&data), &ReadOptions::default()).is_err());
+        let options = ReadOptions { skip_bytes: 16, max_lods: 1, ..Default::default() };
+        assert!(count_faces(&mut Cursor::new(&data), &options).is_err());
+        assert!(read_paths(&mut Cursor::new(&data), &options).is_err());
+        let options = ReadOptions { skip_bytes: 16, magic: *b"MLOD", ..Default::default() };
+        assert!(read_paths(&mut Cursor::new(&data), &options).is_err());
+        let options = ReadOptions { skip_bytes: 16, timeout: Some(Duration::from_secs(0)), ..Default::default() };
+        assert!(matches!(count_faces(&mut Cursor::new(&data), &options), Err(CrowbarError::Timeout(_))));
+    }
+
+    #[test]
+    fn paths_and_counts_assert_the_version() {
+        let data = fixture::Model::new(vec![fixture::Lod::new(1.0)]).to_bytes();
+
+        let options = ReadOptions { versions: vec![71], ..Default::default() };
+        match read_paths(&mut Cursor::new(&data), &options) {
+            Err(CrowbarError::Unsupported(msg)) => assert!(msg.starts_with("Version 73 "), "{}", msg),
+            result => panic!("expected the version to be rejected, got {:?}", result),
+        }
+        assert!(matches!(count_faces(&mut Cursor::new(&data), &options), Err(CrowbarError::Unsupported(_))));
+
+        let options = ReadOptions { versions: vec![71, 73], ..Default::default() };
+        assert!(read_paths(&mut Cursor::new(&data), &options).is_ok());
+        assert_eq!(count_faces(&mut Cursor::new(&data), &options).unwrap(), [0]);
+    }
+
+    #[test]
+    fn dayz_models_are_unsupported() {
+        let mut model = fixture::Model::new(vec![fixture::Lod::new(1.0)]);
+        model.appid = 221100;
+        assert!(matches!(parse_header(&mut Cursor::new(model.to_bytes())), Err(CrowbarError::Unsupported(_))));
+
+        model.appid = 1;
+        let header = parse_header(&mut Cursor::new(model.to_bytes())).unwrap();
+        assert_eq!(header.appid, 1);
+        assert_eq!(header.game, Game::Unknown(1));
+    }
+
+    #[test]
+    fn mlod_version_is_checked() {
+        let data = fixture::Model::new(vec![fixture::Lod::new(1.0)]).to_bytes();
+        assert_eq!(read_odol_from_bytes(&data, &ReadOptions::default()).unwrap().mlod.version, MLOD_VERSION);
+
+        let options = ReadOptions { mlod_version: 256, ..Default::default() };
+        assert!(matches!(read_odol_from_bytes(&data, &options), Err(CrowbarError::Unsupported(_))));
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    #[test]
+    fn gzip_inputs_are_unwrapped() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        let data = fixture::Model::new(vec![fixture::Lod::new(1.0)]).to_bytes();
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&data).unwrap();
+        let mut gzipped = encoder.finish().unwrap();
+
+        let mut reader = unwrap_compressed(Cursor::new(gzipped.clone())).unwrap();
+        assert_eq!(read_odol_from_reader(&mut reader, &ReadOptions::default()).unwrap().mlod.lods.len(), 1);
+
+        let size = gzipped.len() - 4;
+        gzipped[size] ^= 1;
+        assert!(matches!(unwrap_compressed(Cursor::new(gzipped)), Err(CrowbarError::Malformed(_))));
+
+        let mut reader = unwrap_compressed(Cursor::new(data.clone())).unwrap();
+        assert!(read_odol_from_reader(&mut reader, &ReadOptions::default()).is_ok());
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    #[test]
+    fn zstd_inputs_are_unsupported() {
+        let data = [&ZSTD_MAGIC[..], &[0; 16]].concat();
+        assert!(matches!(unwrap_compressed(Cursor::new(data)), Err(CrowbarError::Unsupported(_))));
+    }
+}
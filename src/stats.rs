@@ -0,0 +1,99 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use armake2::p3d::P3D;
+use linked_hash_map::LinkedHashMap;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use serde_json::{Map, Value};
+
+use crate::faces::FaceShape;
+use crate::lod::LodType;
+
+#[derive(Debug, Default)]
+pub struct Stats {
+    pub num_lods: usize,
+    pub num_points: usize,
+    pub num_faces: usize,
+    pub num_tris: usize,
+    pub num_quads: usize,
+    pub num_textures: usize,
+    pub num_materials: usize,
+    pub num_selections: usize,
+    pub lod_types: LinkedHashMap<String, usize>,
+}
+
+impl Stats {
+    pub fn from_p3d(p3d: &P3D) -> Stats {
+        let mut stats = Stats::default();
+        let mut textures: HashSet<&str> = HashSet::new();
+        let mut materials: HashSet<&str> = HashSet::new();
+
+        for lod in p3d.lods.iter() {
+            stats.num_lods += 1;
+            stats.num_points += lod.points.len();
+            stats.num_faces += lod.faces.len();
+            stats.num_selections += lod.taggs.len();
+
+            for face in lod.faces.iter() {
+                if face.is_quad() {
+                    stats.num_quads += 1;
+                } else {
+                    stats.num_tris += 1;
+                }
+
+                if !face.texture.is_empty() {
+                    textures.insert(&face.texture);
+                }
+                if !face.material.is_empty() {
+                    materials.insert(&face.material);
+                }
+            }
+
+            let name = LodType::from_resolution(lod.resolution).name();
+            *stats.lod_types.entry(name.to_string()).or_insert(0) += 1;
+        }
+
+        stats.num_textures = textures.len();
+        stats.num_materials = materials.len();
+        stats
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "lods:        {:>10}", self.num_lods)?;
+        writeln!(f, "points:      {:>10}", self.num_points)?;
+        writeln!(f, "faces:       {:>10}", self.num_faces)?;
+        writeln!(f, "  tris:      {:>10}", self.num_tris)?;
+        writeln!(f, "  quads:     {:>10}", self.num_quads)?;
+        writeln!(f, "textures:    {:>10}", self.num_textures)?;
+        writeln!(f, "materials:   {:>10}", self.num_materials)?;
+        writeln!(f, "selections:  {:>10}", self.num_selections)?;
+        writeln!(f, "lod types:")?;
+        for (name, count) in self.lod_types.iter() {
+            writeln!(f, "  {:<30} {:>4}", name, count)?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for Stats {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let lod_types: Map<String, Value> = self.lod_types.iter()
+            .map(|(name, count)| (name.clone(), Value::from(*count)))
+            .collect();
+
+        let mut json = serializer.serialize_struct("Stats", 9)?;
+        json.serialize_field("num_lods", &self.num_lods)?;
+        json.serialize_field("num_points", &self.num_points)?;
+        json.serialize_field("num_faces", &self.num_faces)?;
+        json.serialize_field("num_tris", &self.num_tris)?;
+        json.serialize_field("num_quads", &self.num_quads)?;
+        json.serialize_field("num_textures", &self.num_textures)?;
+        json.serialize_field("num_materials", &self.num_materials)?;
+        json.serialize_field("num_selections", &self.num_selections)?;
+        json.serialize_field("lod_types", &lod_types)?;
+        json.end()
+    }
+}